@@ -0,0 +1,81 @@
+use crate::future::maybe_done::{maybe_done, MaybeDone};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// 全ての "future" が完了するまで待ち、出力を渡された順に `Vec` で返す
+///
+/// `join!` と違い、実行時に数が決まる "future" をまとめて待つことができる。
+/// 全ての "future" は同じタスクの中でポーリングされるので、並行には動くが並列には動かない。
+///
+/// ```no_run
+/// use mini_tokio::future::join_all;
+/// use mini_tokio::MiniTokio;
+///
+/// let mini_tokio = MiniTokio::new();
+///
+/// mini_tokio.block_on(async {
+///     let futures = (0..3).map(|i| async move { i * 2 });
+///     assert_eq!(join_all(futures).await, vec![0, 2, 4]);
+/// });
+/// ```
+pub fn join_all<I>(iter: I) -> JoinAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    let futures: Vec<_> = iter.into_iter().map(maybe_done).collect();
+
+    JoinAll {
+        futures: futures.into_boxed_slice().into(),
+    }
+}
+
+/// `join_all` が返す "future"
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct JoinAll<F: Future> {
+    // [MEMO]
+    // 要素の "future" をピン留めしたまま扱うため、長さの変わらないスライスを `Box` ごとピン留めする。
+    futures: Pin<Box<[MaybeDone<F>]>>,
+}
+
+impl<F: Future> Future for JoinAll<F> {
+    type Output = Vec<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Vec<F::Output>> {
+        let mut is_pending = false;
+
+        for future in iter_pin_mut(self.futures.as_mut()) {
+            if future.poll(cx).is_pending() {
+                is_pending = true;
+            }
+        }
+
+        if is_pending {
+            return Poll::Pending;
+        }
+
+        let outputs = iter_pin_mut(self.futures.as_mut())
+            .map(|future| future.take_output().unwrap())
+            .collect();
+
+        Poll::Ready(outputs)
+    }
+}
+
+impl<F: Future> fmt::Debug for JoinAll<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinAll")
+            .field("len", &self.futures.len())
+            .finish()
+    }
+}
+
+/// ピン留めされたスライスの要素を、ピン留めされたまま順に返す
+fn iter_pin_mut<T>(slice: Pin<&mut [T]>) -> impl Iterator<Item = Pin<&mut T>> {
+    // SAFETY: 要素を移動させずに、ピン留めしたまま返す
+    unsafe { slice.get_unchecked_mut() }
+        .iter_mut()
+        .map(|item| unsafe { Pin::new_unchecked(item) })
+}
@@ -0,0 +1,67 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// 完了した "future" の出力を、取り出されるまで保持する
+///
+/// `join!` や `join_all` のように、複数の "future" を完了した順に待ち、最後にまとめて出力を返すときに使う。
+pub enum MaybeDone<F: Future> {
+    /// まだ完了していない
+    Future(F),
+    /// 完了して、出力を保持している
+    Done(F::Output),
+    /// 出力が取り出された
+    Gone,
+}
+
+/// `future` を `MaybeDone` で包む
+pub fn maybe_done<F: Future>(future: F) -> MaybeDone<F> {
+    MaybeDone::Future(future)
+}
+
+impl<F: Future> MaybeDone<F> {
+    /// 完了していれば、出力への可変参照を返す
+    pub fn output_mut(self: Pin<&mut Self>) -> Option<&mut F::Output> {
+        // SAFETY: `Done` の出力はピン留めされていない
+        match unsafe { self.get_unchecked_mut() } {
+            MaybeDone::Done(output) => Some(output),
+            _ => None,
+        }
+    }
+
+    /// 完了していれば、出力を取り出す
+    pub fn take_output(self: Pin<&mut Self>) -> Option<F::Output> {
+        // SAFETY: `Future` の場合は何もしないので、ピン留めされた "future" が移動することはない
+        let this = unsafe { self.get_unchecked_mut() };
+        match this {
+            MaybeDone::Done(_) => {}
+            MaybeDone::Future(_) | MaybeDone::Gone => return None,
+        }
+
+        match std::mem::replace(this, MaybeDone::Gone) {
+            MaybeDone::Done(output) => Some(output),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<F: Future> Future for MaybeDone<F> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // SAFETY: `Future` の中身は移動させずにポーリングし、完了したら破棄してから出力に置き換える
+        let this = unsafe { self.get_unchecked_mut() };
+
+        let output = match this {
+            MaybeDone::Future(future) => match unsafe { Pin::new_unchecked(future) }.poll(cx) {
+                Poll::Ready(output) => output,
+                Poll::Pending => return Poll::Pending,
+            },
+            MaybeDone::Done(_) => return Poll::Ready(()),
+            MaybeDone::Gone => panic!("MaybeDone polled after value taken"),
+        };
+
+        *this = MaybeDone::Done(output);
+        Poll::Ready(())
+    }
+}
@@ -0,0 +1,12 @@
+//! 複数の "future" を組み合わせて待つための関数
+//!
+//! 数が決まっている "future" を待つときは `join!` や `select!` マクロを、
+//! 実行時に数が決まる "future" を待つときは、ここにある関数を使う。
+
+pub(crate) mod maybe_done;
+
+mod join_all;
+pub use join_all::{join_all, JoinAll};
+
+mod select_all;
+pub use select_all::{select_all, SelectAll};
@@ -0,0 +1,86 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// 渡された "future" のうち、最初に完了したものの出力を返す
+///
+/// 出力と一緒に、完了した "future" の位置と、まだ完了していない残りの "future" を返す。
+/// 残りの "future" をもう一度 `select_all` に渡せば、続けて次に完了するものを待てる。
+/// 残りの "future" の順番は保たれない。
+///
+/// [MEMO]
+/// 残りの "future" を返すために要素を移動させるので、`Unpin` な "future" しか受け取れない。
+/// `async` ブロックを渡すときは `Box::pin` で包む。
+///
+/// # Panics
+///
+/// `iter` が空の場合はパニックする。
+///
+/// ```no_run
+/// use mini_tokio::future::select_all;
+/// use mini_tokio::MiniTokio;
+///
+/// let mini_tokio = MiniTokio::new();
+///
+/// mini_tokio.block_on(async {
+///     let futures = (0..3).map(|i| Box::pin(async move { i * 2 }));
+///
+///     let (output, index, remaining) = select_all(futures).await;
+///     assert_eq!((output, index), (0, 0));
+///     assert_eq!(remaining.len(), 2);
+/// });
+/// ```
+pub fn select_all<I>(iter: I) -> SelectAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Future + Unpin,
+{
+    let futures: Vec<_> = iter.into_iter().collect();
+    assert!(
+        !futures.is_empty(),
+        "select_all requires at least one future"
+    );
+
+    SelectAll { futures }
+}
+
+/// `select_all` が返す "future"
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct SelectAll<F> {
+    futures: Vec<F>,
+}
+
+impl<F: Future + Unpin> Future for SelectAll<F> {
+    type Output = (F::Output, usize, Vec<F>);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let ready = self
+            .futures
+            .iter_mut()
+            .enumerate()
+            .find_map(|(index, future)| match Pin::new(future).poll(cx) {
+                Poll::Ready(output) => Some((index, output)),
+                Poll::Pending => None,
+            });
+
+        match ready {
+            Some((index, output)) => {
+                // 完了した "future" を取り除き、残りを返す
+                drop(self.futures.swap_remove(index));
+                let remaining = std::mem::take(&mut self.futures);
+
+                Poll::Ready((output, index, remaining))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<F> fmt::Debug for SelectAll<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SelectAll")
+            .field("len", &self.futures.len())
+            .finish()
+    }
+}
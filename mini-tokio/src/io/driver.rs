@@ -0,0 +1,180 @@
+use super::scheduled_io::ScheduledIo;
+use mio::event::Source;
+use mio::{Events, Interest, Poll, Registry, Token, Waker};
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{self, AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// `unpark` で epoll の待機を中断するための `mio::Waker` のトークン
+const WAKE_TOKEN: Token = Token(usize::MAX);
+
+/// epoll で I/O イベントを待ち、準備ができたソケットを待っているタスクを呼び起こす
+///
+/// ドライバは独自のスレッドを持たない。実行するタスクがなくなったワーカーのうち1つが、
+/// `try_park` で epoll を待つ役割を引き受ける。
+pub(crate) struct Driver {
+    // epoll で待てるのは一度に1つのワーカーだけなので、`Mutex` で保護する
+    poller: Mutex<Poller>,
+    // ソケットの登録・解除は、epoll で待っている間も他のスレッドから行える
+    registry: Registry,
+    waker: Waker,
+    // ワーカーが epoll で待っているかどうか
+    parked: AtomicBool,
+    resources: Mutex<Resources>,
+}
+
+struct Poller {
+    poll: Poll,
+    events: Events,
+}
+
+struct Resources {
+    map: HashMap<Token, Arc<ScheduledIo>>,
+    next_token: usize,
+}
+
+impl Driver {
+    pub(crate) fn new() -> io::Result<Driver> {
+        let poll = Poll::new()?;
+        let registry = poll.registry().try_clone()?;
+        let waker = Waker::new(poll.registry(), WAKE_TOKEN)?;
+
+        Ok(Driver {
+            poller: Mutex::new(Poller {
+                poll,
+                events: Events::with_capacity(1024),
+            }),
+            registry,
+            waker,
+            parked: AtomicBool::new(false),
+            resources: Mutex::new(Resources {
+                map: HashMap::new(),
+                next_token: 0,
+            }),
+        })
+    }
+
+    /// ソケットを epoll に登録する
+    pub(super) fn register(
+        &self,
+        source: &mut impl Source,
+        interest: Interest,
+    ) -> io::Result<(Token, Arc<ScheduledIo>)> {
+        let shared = Arc::new(ScheduledIo::new());
+        let token = {
+            let mut resources = self.resources.lock().unwrap();
+            let token = Token(resources.next_token);
+            resources.next_token += 1;
+            resources.map.insert(token, shared.clone());
+            token
+        };
+
+        if let Err(e) = self.registry.register(source, token, interest) {
+            self.resources.lock().unwrap().map.remove(&token);
+            return Err(e);
+        }
+
+        Ok((token, shared))
+    }
+
+    /// `register` で登録したソケットの情報を取り除く
+    ///
+    /// [MEMO]
+    /// ソケットを閉じると epoll からは自動的に取り除かれるので、ここではドライバ側の情報だけを取り除く。
+    pub(super) fn deregister(&self, token: Token) {
+        let removed = self.resources.lock().unwrap().map.remove(&token);
+        drop(removed);
+    }
+
+    /// 他のワーカーが epoll で待っていなければ、`deadline` まで I/O イベントを待つ
+    ///
+    /// epoll で待つ役割を引き受けた場合は `true`、他のワーカーが待っていた場合は何もせず `false` を返す。
+    pub(crate) fn try_park(&self, deadline: Option<Instant>, has_work: impl Fn() -> bool) -> bool {
+        let Ok(mut poller) = self.poller.try_lock() else {
+            return false;
+        };
+
+        self.parked.store(true, Ordering::SeqCst);
+
+        // [MEMO]
+        // `parked` を立ててから改めてキューを確認する。
+        // `unpark` 側はタスクをプッシュしてから `parked` を確認するので、起こし忘れが起きない。
+        atomic::fence(Ordering::SeqCst);
+
+        let timeout = if has_work() {
+            Some(Duration::ZERO)
+        } else {
+            deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+        };
+
+        self.poll(&mut poller, timeout);
+        true
+    }
+
+    /// 待たずに I/O イベントを確認する
+    ///
+    /// タスクが途切れないワーカーが、定期的に I/O イベントを処理するために呼ぶ。
+    pub(crate) fn poll_now(&self) {
+        if let Ok(mut poller) = self.poller.try_lock() {
+            self.poll(&mut poller, Some(Duration::ZERO));
+        }
+    }
+
+    fn poll(&self, poller: &mut Poller, timeout: Option<Duration>) {
+        let Poller { poll, events } = poller;
+
+        let res = poll.poll(events, timeout);
+        self.parked.store(false, Ordering::SeqCst);
+
+        match res {
+            Ok(()) => {}
+            // シグナルによる中断は、イベントがなかったものとして扱う
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return,
+            Err(e) => panic!("unexpected error when polling the I/O driver: {:?}", e),
+        }
+
+        for event in events.iter() {
+            if event.token() == WAKE_TOKEN {
+                continue;
+            }
+
+            let resource = self
+                .resources
+                .lock()
+                .unwrap()
+                .map
+                .get(&event.token())
+                .cloned();
+            if let Some(resource) = resource {
+                resource.set_readiness(event);
+            }
+        }
+    }
+
+    /// epoll で待っているワーカーがいれば起こす
+    pub(crate) fn unpark(&self) {
+        atomic::fence(Ordering::SeqCst);
+
+        if self.parked.load(Ordering::SeqCst) {
+            let _ = self.waker.wake();
+        }
+    }
+
+    /// ランタイムの停止時に、ソケットを待っているタスクの "waker" を全て破棄する
+    pub(crate) fn shutdown(&self) {
+        let resources: Vec<_> = self
+            .resources
+            .lock()
+            .unwrap()
+            .map
+            .values()
+            .cloned()
+            .collect();
+
+        for resource in resources {
+            resource.clear_wakers();
+        }
+    }
+}
@@ -0,0 +1,12 @@
+//! epoll によってファイルディスクリプタの準備完了を待つ I/O ドライバ
+//!
+//! `mio` を通して epoll を利用する。ソケットは `Registration` によってドライバに登録され、
+//! 読み書きが可能になると、待っているタスクの "waker" が呼び起こされる。
+
+mod driver;
+pub(crate) use driver::Driver;
+
+mod registration;
+pub(crate) use registration::{Direction, Registration};
+
+mod scheduled_io;
@@ -0,0 +1,90 @@
+use super::scheduled_io::{ReadyEvent, ScheduledIo};
+use crate::runtime::Handle;
+use mio::event::Source;
+use mio::{Interest, Token};
+use std::future;
+use std::io;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+/// ソケットの操作の方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Read,
+    Write,
+}
+
+/// I/O ドライバにソケットを登録し、準備ができるのを待つためのハンドル
+///
+/// 破棄されるとドライバから登録が取り除かれる。
+pub(crate) struct Registration {
+    handle: Handle,
+    token: Token,
+    shared: Arc<ScheduledIo>,
+}
+
+impl Registration {
+    /// 現在のランタイムの I/O ドライバに `source` を登録する
+    ///
+    /// # Panics
+    ///
+    /// ランタイムの外から呼び出された場合はパニックする。
+    pub(crate) fn new(source: &mut impl Source, interest: Interest) -> io::Result<Registration> {
+        let handle = Handle::current();
+        let (token, shared) = handle.io().register(source, interest)?;
+
+        Ok(Registration {
+            handle,
+            token,
+            shared,
+        })
+    }
+
+    /// `direction` の準備ができるのを待つ
+    pub(crate) fn poll_ready(
+        &self,
+        cx: &mut Context<'_>,
+        direction: Direction,
+    ) -> Poll<ReadyEvent> {
+        self.shared.poll_ready(cx, direction)
+    }
+
+    /// 準備ができたら `f` を呼び出し、`WouldBlock` が返った場合は再び準備ができるのを待つ
+    ///
+    /// `f` にはノンブロッキングなソケットへの操作を渡す。
+    pub(crate) fn poll_io<R>(
+        &self,
+        cx: &mut Context<'_>,
+        direction: Direction,
+        mut f: impl FnMut() -> io::Result<R>,
+    ) -> Poll<io::Result<R>> {
+        loop {
+            let event = ready!(self.poll_ready(cx, direction));
+
+            match f() {
+                // [MEMO]
+                // epoll はエッジトリガーで登録しているため、`WouldBlock` が返るまで操作を続ける必要がある。
+                // `WouldBlock` が返ったら準備状態を取り消し、次のイベントを待つ。
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    self.shared.clear_readiness(event);
+                }
+                res => return Poll::Ready(res),
+            }
+        }
+    }
+
+    /// `poll_io` の async 版
+    pub(crate) async fn async_io<R>(
+        &self,
+        direction: Direction,
+        mut f: impl FnMut() -> io::Result<R>,
+    ) -> io::Result<R> {
+        future::poll_fn(|cx| self.poll_io(cx, direction, &mut f)).await
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.handle.io().deregister(self.token);
+    }
+}
@@ -0,0 +1,156 @@
+use std::mem;
+use std::sync::Mutex;
+use std::task::{Context, Poll, Waker};
+
+use super::Direction;
+
+// 読み込み可能
+const READABLE: u8 = 0b0001;
+// 書き込み可能
+const WRITABLE: u8 = 0b0010;
+// 相手が書き込み側を閉じた（これ以上読み込むデータがない）
+const READ_CLOSED: u8 = 0b0100;
+// 書き込み側が閉じられた
+const WRITE_CLOSED: u8 = 0b1000;
+
+/// ドライバに登録された1つのソケットの準備状態と、それを待っているタスクの "waker"
+pub(super) struct ScheduledIo {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    // 準備ができている方向のビット
+    readiness: u8,
+    // イベントを受け取るたびに増える番号
+    // [MEMO]
+    // `clear_readiness` の直前に新しいイベントが届いていた場合に、そのイベントまで消してしまわないために使う。
+    tick: u64,
+    // 読み込み・書き込みの準備を待っているタスクの "waker"
+    // [MEMO]
+    // 以前は方向ごとに "waker" を1つだけ保存していたため、`Arc<TcpListener>` を複数のタスクで `accept` するなど、
+    // 同じ方向を2つ以上のタスクが待つと、先に待っていたタスクの "waker" が上書きされて二度と起こされなかった。
+    // 待っている "future" が途中で破棄されても "waker" は残るが、次のイベントで起こされて取り除かれる。
+    readers: Vec<Waker>,
+    writers: Vec<Waker>,
+}
+
+/// `poll_ready` が観測した準備状態
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ReadyEvent {
+    readiness: u8,
+    tick: u64,
+}
+
+impl Direction {
+    fn mask(self) -> u8 {
+        match self {
+            Direction::Read => READABLE | READ_CLOSED,
+            Direction::Write => WRITABLE | WRITE_CLOSED,
+        }
+    }
+}
+
+impl ScheduledIo {
+    pub(super) fn new() -> ScheduledIo {
+        ScheduledIo {
+            inner: Mutex::new(Inner {
+                readiness: 0,
+                tick: 0,
+                readers: Vec::new(),
+                writers: Vec::new(),
+            }),
+        }
+    }
+
+    /// epoll から受け取ったイベントを反映し、準備ができた方向を待っているタスクを呼び起こす
+    pub(super) fn set_readiness(&self, event: &mio::event::Event) {
+        let mut readiness = 0;
+
+        // [MEMO]
+        // エラーが起きた場合は、読み書きの操作からエラーを返せるよう両方向とも準備完了として扱う。
+        if event.is_readable() || event.is_error() {
+            readiness |= READABLE;
+        }
+        if event.is_writable() || event.is_error() {
+            readiness |= WRITABLE;
+        }
+        if event.is_read_closed() {
+            readiness |= READ_CLOSED;
+        }
+        if event.is_write_closed() {
+            readiness |= WRITE_CLOSED;
+        }
+
+        let (readers, writers) = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.readiness |= readiness;
+            inner.tick = inner.tick.wrapping_add(1);
+
+            let readers = if inner.readiness & Direction::Read.mask() != 0 {
+                mem::take(&mut inner.readers)
+            } else {
+                Vec::new()
+            };
+            let writers = if inner.readiness & Direction::Write.mask() != 0 {
+                mem::take(&mut inner.writers)
+            } else {
+                Vec::new()
+            };
+            (readers, writers)
+        };
+
+        // ロックを解放してから "waker" を呼び出す
+        for waker in readers.into_iter().chain(writers) {
+            waker.wake();
+        }
+    }
+
+    /// `direction` の準備ができていれば `Ready` を返す。できていなければ "waker" を保存して `Pending` を返す
+    pub(super) fn poll_ready(
+        &self,
+        cx: &mut Context<'_>,
+        direction: Direction,
+    ) -> Poll<ReadyEvent> {
+        let mut inner = self.inner.lock().unwrap();
+
+        let readiness = inner.readiness & direction.mask();
+        if readiness != 0 {
+            return Poll::Ready(ReadyEvent {
+                readiness,
+                tick: inner.tick,
+            });
+        }
+
+        let wakers = match direction {
+            Direction::Read => &mut inner.readers,
+            Direction::Write => &mut inner.writers,
+        };
+        // 同じタスクが何度ポーリングしても、"waker" は1つだけ保存する
+        if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+
+        Poll::Pending
+    }
+
+    /// 操作が `WouldBlock` を返したときに、観測した準備状態を取り消す
+    ///
+    /// `event` を観測した後に新しいイベントが届いていた場合は、何もしない。
+    pub(super) fn clear_readiness(&self, event: ReadyEvent) {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.tick == event.tick {
+            // 閉じられた状態は元に戻らないので、取り消さない
+            inner.readiness &= !(event.readiness & (READABLE | WRITABLE));
+        }
+    }
+
+    /// 保存している "waker" を全て破棄する
+    pub(super) fn clear_wakers(&self) {
+        let (readers, writers) = {
+            let mut inner = self.inner.lock().unwrap();
+            (mem::take(&mut inner.readers), mem::take(&mut inner.writers))
+        };
+        drop((readers, writers));
+    }
+}
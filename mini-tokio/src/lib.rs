@@ -0,0 +1,18 @@
+//! Tokio の仕組みを学習するための、最小限の非同期ランタイム
+
+pub mod future;
+mod io;
+#[doc(hidden)]
+pub mod macros;
+pub mod net;
+pub mod runtime;
+pub mod sync;
+mod task;
+pub mod time;
+
+pub use runtime::MiniTokio;
+pub use task::{
+    spawn, spawn_blocking, AbortHandle, AccessError, JoinError, JoinHandle, LocalKey,
+    TaskLocalFuture,
+};
+pub use time::Delay;
@@ -0,0 +1,79 @@
+/// 複数の "future" を並行に待ち、全ての出力をタプルで返す
+///
+/// 全ての "future" は `join!` を呼んだタスクの中でポーリングされる。
+/// 並行には動くが、別々のスレッドで並列に動くわけではない。並列に動かしたい場合は `spawn` する。
+///
+/// ```no_run
+/// use mini_tokio::MiniTokio;
+///
+/// async fn one() -> u32 {
+///     1
+/// }
+///
+/// async fn two() -> &'static str {
+///     "two"
+/// }
+///
+/// let mini_tokio = MiniTokio::new();
+///
+/// mini_tokio.block_on(async {
+///     let (a, b) = mini_tokio::join!(one(), two());
+///     assert_eq!((a, b), (1, "two"));
+/// });
+/// ```
+#[macro_export]
+macro_rules! join {
+    // [MEMO]
+    // マクロの中では "future" に連番の名前を付けられないので、"future" をタプルにまとめ、
+    // 前にある要素の数だけ `_` を並べたパターン `(_, _, fut, ..)` で i 番目の要素を取り出す。
+    // `( $($skip)* )` には、その分岐より前にある分岐の数だけ `_` が入っている。
+
+    // 全ての分岐を正規化した後の展開
+    (@ {
+        ( $($count:tt)* )
+        $( ( $($skip:tt)* ) $e:expr, )*
+    }) => {{
+        let mut futures = ( $( $crate::macros::support::maybe_done($e), )* );
+        // `futures` をシャドーイングして、以降は移動できないようにする
+        let futures = &mut futures;
+
+        $crate::macros::support::poll_fn(move |cx| {
+            let mut is_pending = false;
+
+            $(
+                let ( $($skip,)* future, .. ) = &mut *futures;
+                // SAFETY: `futures` はシャドーイングされていて、完了するまで移動しない
+                let future = unsafe { $crate::macros::support::Pin::new_unchecked(future) };
+                if $crate::macros::support::Future::poll(future, cx).is_pending() {
+                    is_pending = true;
+                }
+            )*
+
+            if is_pending {
+                return $crate::macros::support::Poll::Pending;
+            }
+
+            $crate::macros::support::Poll::Ready(($({
+                let ( $($skip,)* future, .. ) = &mut *futures;
+                // SAFETY: 同上
+                let future = unsafe { $crate::macros::support::Pin::new_unchecked(future) };
+                future.take_output().expect("expected completed future")
+            },)*))
+        })
+        .await
+    }};
+
+    // 分岐を1つずつ正規化する
+    (@ { ( $($s:tt)* ) $($t:tt)* } $e:expr, $($r:tt)* ) => {
+        $crate::join!(@{ ($($s)* _) $($t)* ($($s)*) $e, } $($r)*)
+    };
+
+    // 入口
+    ( $($e:expr),+ $(,)? ) => {
+        $crate::join!(@{ () } $($e,)+)
+    };
+
+    () => {
+        async {}.await
+    };
+}
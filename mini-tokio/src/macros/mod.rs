@@ -0,0 +1,12 @@
+//! 複数の "future" を組み合わせて待つためのマクロと、タスクごとの値を宣言するマクロ
+//!
+//! `join!` は全ての "future" を、`select!` は最初に完了した "future" を待つ。
+//! `task_local!` はタスクごとに異なる値を持つ `LocalKey` を宣言する。
+
+mod join;
+mod select;
+mod task_local;
+mod try_join;
+
+#[doc(hidden)]
+pub mod support;
@@ -0,0 +1,223 @@
+/// 複数の "future" を並行に待ち、最初に完了した分岐のハンドラを実行する
+///
+/// ```text
+/// select! {
+///     <パターン> = <"future"> (, if <前提条件>)? => <ハンドラ>,
+///     ...
+///     (else => <式>)?
+/// }
+/// ```
+///
+/// 1. 全ての前提条件を評価し、`false` になった分岐を無効にする。
+/// 2. 全ての分岐の "future" を作る。無効な分岐の "future" も作られるが、ポーリングはされない。
+/// 3. 有効な分岐の "future" をポーリングし、完了したものの出力をパターンと照合する。
+///    パターンに一致すればハンドラを実行し、一致しなければその分岐を無効にして残りの分岐を待ち続ける。
+/// 4. 全ての分岐が無効になった場合は、`else` の式を評価する。`else` がなければパニックする。
+///
+/// 完了しなかった分岐の "future" は、ハンドラを実行する前に破棄される。
+///
+/// 分岐は毎回ランダムな位置からポーリングされるので、特定の分岐ばかりが選ばれることはない。
+/// 先頭に `biased;` を書くと、書いた順にポーリングする。
+///
+/// [MEMO]
+/// ハンドラは "future" をポーリングする `poll_fn` の外で実行されるので、
+/// ハンドラの中で `.await` したり、外側のループに対して `break` や `continue` を使ったりできる。
+///
+/// [MEMO]
+/// 出力がパターンに一致するかどうかは、出力を移動させないように参照に対して照合して確かめる。
+/// そのため、`Some(mut v)` のようにパターンの内側で値を移動させて束縛するパターンは使えない。
+/// `Some(v)` で束縛してから、ハンドラの中で `let mut v = v;` とする。
+///
+/// ```no_run
+/// use mini_tokio::sync::oneshot;
+/// use mini_tokio::MiniTokio;
+///
+/// let mini_tokio = MiniTokio::new();
+///
+/// mini_tokio.block_on(async {
+///     let (tx1, rx1) = oneshot::channel();
+///     let (tx2, rx2) = oneshot::channel();
+///
+///     mini_tokio::spawn(async move {
+///         let _ = tx1.send("one");
+///     });
+///
+///     mini_tokio::spawn(async move {
+///         let _ = tx2.send("two");
+///     });
+///
+///     mini_tokio::select! {
+///         Ok(val) = rx1 => println!("rx1 completed first with {:?}", val),
+///         Ok(val) = rx2 => println!("rx2 completed first with {:?}", val),
+///         else => println!("both senders were dropped"),
+///     }
+/// });
+/// ```
+#[macro_export]
+macro_rules! select {
+    // [MEMO]
+    // 分岐の取り出し方は `join!` と同じ。
+    // 完了した分岐の出力は、分岐の位置の数だけ `Err` を重ねた `Result` に入れて `poll_fn` の外に返す。
+    // 例えば3番目の分岐の出力 `v` は `Err(Err(Ok(v)))` になり、全ての分岐が無効になったことは
+    // 分岐の数だけ `Err` を重ねた `Err(Err(Err(())))` で表す。
+
+    // 全ての分岐を正規化した後の展開
+    (@ {
+        start=$start:expr;
+        ( $($count:tt)* )
+        $( ( $($skip:tt)* ) $bind:pat = $fut:expr, if $c:expr => $handle:expr, )+
+        ; $else:expr
+    }) => {{
+        const BRANCHES: u32 = $crate::__select_count!($($count)*);
+
+        // 無効になった分岐のビットを立てる
+        let mut disabled: u64 = 0;
+        $(
+            if !$c {
+                disabled |= 1 << $crate::__select_count!($($skip)*);
+            }
+        )+
+
+        let output = {
+            let mut futures = ( $( $fut, )+ );
+            // `futures` をシャドーイングして、以降は移動できないようにする
+            let futures = &mut futures;
+
+            $crate::macros::support::poll_fn(|cx| {
+                let mut is_pending = false;
+                let start: u32 = $start;
+
+                for i in 0..BRANCHES {
+                    let branch = (start + i) % BRANCHES;
+
+                    $(
+                        if branch == $crate::__select_count!($($skip)*) && disabled & (1 << branch) == 0 {
+                            let ( $($skip,)* future, .. ) = &mut *futures;
+                            // SAFETY: `futures` はシャドーイングされていて、`select!` が返るまで移動しない
+                            let future = unsafe { $crate::macros::support::Pin::new_unchecked(future) };
+
+                            match $crate::macros::support::Future::poll(future, cx) {
+                                $crate::macros::support::Poll::Ready(output) => {
+                                    // 完了した分岐は、もうポーリングしない
+                                    disabled |= 1 << branch;
+
+                                    #[allow(unused_variables, unused_mut, unreachable_patterns)]
+                                    let matched = matches!(&output, $bind);
+
+                                    if matched {
+                                        return $crate::macros::support::Poll::Ready(
+                                            $crate::__select_wrap!(($($skip)*) output),
+                                        );
+                                    }
+                                }
+                                $crate::macros::support::Poll::Pending => {
+                                    is_pending = true;
+                                }
+                            }
+                        }
+                    )+
+                }
+
+                if is_pending {
+                    $crate::macros::support::Poll::Pending
+                } else {
+                    // 全ての分岐が無効になった
+                    $crate::macros::support::Poll::Ready($crate::__select_disabled!(($($count)*)))
+                }
+            })
+            .await
+        };
+
+        match output {
+            $(
+                $crate::__select_wrap!(($($skip)*) $bind) => $handle,
+            )+
+            $crate::__select_disabled!(($($count)*)) => $else,
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("failed to match bind"),
+        }
+    }};
+
+    // 分岐を1つずつ正規化する
+    // [MEMO]
+    // 正規化した分岐は `( 前の分岐の数だけの _ ) パターン = "future", if 前提条件 => ハンドラ,` の形にそろえる。
+
+    // `else` がない
+    (@ { start=$start:expr; $($t:tt)* } ) => {
+        $crate::select!(@{ start=$start; $($t)*; panic!("all branches are disabled and there is no else branch") })
+    };
+    (@ { start=$start:expr; $($t:tt)* } else => $else:expr $(,)?) => {
+        $crate::select!(@{ start=$start; $($t)*; $else })
+    };
+    (@ { start=$start:expr; ( $($s:tt)* ) $($t:tt)* } $p:pat = $f:expr, if $c:expr => $h:block, $($r:tt)* ) => {
+        $crate::select!(@{ start=$start; ($($s)* _) $($t)* ($($s)*) $p = $f, if $c => $h, } $($r)*)
+    };
+    (@ { start=$start:expr; ( $($s:tt)* ) $($t:tt)* } $p:pat = $f:expr => $h:block, $($r:tt)* ) => {
+        $crate::select!(@{ start=$start; ($($s)* _) $($t)* ($($s)*) $p = $f, if true => $h, } $($r)*)
+    };
+    (@ { start=$start:expr; ( $($s:tt)* ) $($t:tt)* } $p:pat = $f:expr, if $c:expr => $h:block $($r:tt)* ) => {
+        $crate::select!(@{ start=$start; ($($s)* _) $($t)* ($($s)*) $p = $f, if $c => $h, } $($r)*)
+    };
+    (@ { start=$start:expr; ( $($s:tt)* ) $($t:tt)* } $p:pat = $f:expr => $h:block $($r:tt)* ) => {
+        $crate::select!(@{ start=$start; ($($s)* _) $($t)* ($($s)*) $p = $f, if true => $h, } $($r)*)
+    };
+    (@ { start=$start:expr; ( $($s:tt)* ) $($t:tt)* } $p:pat = $f:expr, if $c:expr => $h:expr ) => {
+        $crate::select!(@{ start=$start; ($($s)* _) $($t)* ($($s)*) $p = $f, if $c => $h, })
+    };
+    (@ { start=$start:expr; ( $($s:tt)* ) $($t:tt)* } $p:pat = $f:expr => $h:expr ) => {
+        $crate::select!(@{ start=$start; ($($s)* _) $($t)* ($($s)*) $p = $f, if true => $h, })
+    };
+    (@ { start=$start:expr; ( $($s:tt)* ) $($t:tt)* } $p:pat = $f:expr, if $c:expr => $h:expr, $($r:tt)* ) => {
+        $crate::select!(@{ start=$start; ($($s)* _) $($t)* ($($s)*) $p = $f, if $c => $h, } $($r)*)
+    };
+    (@ { start=$start:expr; ( $($s:tt)* ) $($t:tt)* } $p:pat = $f:expr => $h:expr, $($r:tt)* ) => {
+        $crate::select!(@{ start=$start; ($($s)* _) $($t)* ($($s)*) $p = $f, if true => $h, } $($r)*)
+    };
+
+    // 入口
+    (biased; $p:pat = $($t:tt)* ) => {
+        $crate::select!(@{ start=0; () } $p = $($t)*)
+    };
+    ( $p:pat = $($t:tt)* ) => {
+        $crate::select!(@{ start={ $crate::macros::support::thread_rng_n(BRANCHES) }; () } $p = $($t)*)
+    };
+    () => {
+        compile_error!("select! requires at least one branch.")
+    };
+}
+
+/// `_` の数を数える
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __select_count {
+    () => {
+        0
+    };
+    (_ $($t:tt)*) => {
+        1 + $crate::__select_count!($($t)*)
+    };
+}
+
+/// `_` の数だけ `Err` を重ねて、最後に `Ok` で包む
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __select_wrap {
+    (() $($e:tt)*) => {
+        ::std::result::Result::Ok($($e)*)
+    };
+    ((_ $($s:tt)*) $($e:tt)*) => {
+        ::std::result::Result::Err($crate::__select_wrap!(($($s)*) $($e)*))
+    };
+}
+
+/// `_` の数だけ `Err` を重ねて、`()` を包む
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __select_disabled {
+    (()) => {
+        ()
+    };
+    ((_ $($s:tt)*)) => {
+        ::std::result::Result::Err($crate::__select_disabled!(($($s)*)))
+    };
+}
@@ -0,0 +1,13 @@
+//! マクロの展開先から参照する型や関数
+//!
+//! マクロは利用する側のクレートで展開されるので、`$crate::macros::support` を通して参照する。
+
+pub use crate::future::maybe_done::{maybe_done, MaybeDone};
+pub use std::future::{poll_fn, Future};
+pub use std::pin::Pin;
+pub use std::task::Poll;
+
+/// `0..n` の範囲の乱数を返す
+pub fn thread_rng_n(n: u32) -> u32 {
+    crate::runtime::rand::thread_rng_n(n)
+}
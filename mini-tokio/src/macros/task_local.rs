@@ -0,0 +1,61 @@
+/// タスクごとに異なる値を持つ `LocalKey` を宣言する
+///
+/// 値は `LocalKey::scope` で設定し、その "future" の中から `with` や `get` で参照する。
+/// スレッドローカルと違い、タスクが別のワーカースレッドに移っても同じ値を参照できる。
+///
+/// ```no_run
+/// use mini_tokio::MiniTokio;
+///
+/// mini_tokio::task_local! {
+///     pub static REQUEST_ID: u64;
+///     static USER: String;
+/// }
+///
+/// async fn handle() {
+///     REQUEST_ID.with(|id| println!("handling request {}", id));
+/// }
+///
+/// let mini_tokio = MiniTokio::new();
+///
+/// mini_tokio.block_on(async {
+///     let handles: Vec<_> = (0..3)
+///         .map(|id| mini_tokio::spawn(REQUEST_ID.scope(id, handle())))
+///         .collect();
+///
+///     for handle in handles {
+///         handle.await.unwrap();
+///     }
+/// });
+/// ```
+#[macro_export]
+macro_rules! task_local {
+    () => {};
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty; $($rest:tt)*) => {
+        $crate::__task_local_inner!($(#[$attr])* $vis $name, $t);
+        $crate::task_local!($($rest)*);
+    };
+
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty) => {
+        $crate::__task_local_inner!($(#[$attr])* $vis $name, $t);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __task_local_inner {
+    ($(#[$attr:meta])* $vis:vis $name:ident, $t:ty) => {
+        // [MEMO]
+        // 値はポーリングしている間だけスレッドローカルに置くので、中身はスレッドローカルで保持する。
+        // `thread_local!` はキーを定数として宣言するので、`LocalKey` の初期値に使うことができる。
+        $(#[$attr])*
+        $vis static $name: $crate::LocalKey<$t> = {
+            ::std::thread_local! {
+                static __KEY: ::std::cell::RefCell<::std::option::Option<$t>> =
+                    const { ::std::cell::RefCell::new(::std::option::Option::None) };
+            }
+
+            $crate::LocalKey { inner: __KEY }
+        };
+    };
+}
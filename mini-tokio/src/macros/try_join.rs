@@ -0,0 +1,81 @@
+/// `Result` を返す複数の "future" を並行に待ち、全てが `Ok` であれば出力をタプルで返す
+///
+/// いずれかの "future" が `Err` を返した時点で、残りの "future" を待たずにそのエラーを返す。
+/// 残りの "future" は、`try_join!` が返るときに破棄される。
+///
+/// ```no_run
+/// use mini_tokio::MiniTokio;
+///
+/// async fn parse(s: &str) -> Result<u32, std::num::ParseIntError> {
+///     s.parse()
+/// }
+///
+/// let mini_tokio = MiniTokio::new();
+///
+/// mini_tokio.block_on(async {
+///     let res = mini_tokio::try_join!(parse("1"), parse("2"));
+///     assert_eq!(res, Ok((1, 2)));
+///
+///     let res = mini_tokio::try_join!(parse("1"), parse("x"));
+///     assert!(res.is_err());
+/// });
+/// ```
+#[macro_export]
+macro_rules! try_join {
+    // [MEMO]
+    // 分岐の取り出し方は `join!` と同じ。
+
+    // 全ての分岐を正規化した後の展開
+    (@ {
+        ( $($count:tt)* )
+        $( ( $($skip:tt)* ) $e:expr, )*
+    }) => {{
+        let mut futures = ( $( $crate::macros::support::maybe_done($e), )* );
+        // `futures` をシャドーイングして、以降は移動できないようにする
+        let futures = &mut futures;
+
+        $crate::macros::support::poll_fn(move |cx| {
+            let mut is_pending = false;
+
+            $(
+                let ( $($skip,)* future, .. ) = &mut *futures;
+                // SAFETY: `futures` はシャドーイングされていて、完了するまで移動しない
+                let mut future = unsafe { $crate::macros::support::Pin::new_unchecked(future) };
+                if $crate::macros::support::Future::poll(future.as_mut(), cx).is_pending() {
+                    is_pending = true;
+                } else if future.as_mut().output_mut().expect("expected completed future").is_err() {
+                    // エラーになった時点で、残りの "future" を待たずに返す
+                    return $crate::macros::support::Poll::Ready(
+                        ::std::result::Result::Err(future.take_output().unwrap().err().unwrap()),
+                    );
+                }
+            )*
+
+            if is_pending {
+                return $crate::macros::support::Poll::Pending;
+            }
+
+            $crate::macros::support::Poll::Ready(::std::result::Result::Ok(($({
+                let ( $($skip,)* future, .. ) = &mut *futures;
+                // SAFETY: 同上
+                let future = unsafe { $crate::macros::support::Pin::new_unchecked(future) };
+                future.take_output().unwrap().ok().unwrap()
+            },)*)))
+        })
+        .await
+    }};
+
+    // 分岐を1つずつ正規化する
+    (@ { ( $($s:tt)* ) $($t:tt)* } $e:expr, $($r:tt)* ) => {
+        $crate::try_join!(@{ ($($s)* _) $($t)* ($($s)*) $e, } $($r)*)
+    };
+
+    // 入口
+    ( $($e:expr),+ $(,)? ) => {
+        $crate::try_join!(@{ () } $($e,)+)
+    };
+
+    () => {
+        async { ::std::result::Result::Ok(()) }.await
+    };
+}
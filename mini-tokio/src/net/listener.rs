@@ -0,0 +1,119 @@
+use super::{each_addr, TcpStream};
+use crate::io::{Direction, Registration};
+use mio::Interest;
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+
+/// 接続を待ち受ける TCP ソケット
+///
+/// ```no_run
+/// use mini_tokio::net::TcpListener;
+/// use mini_tokio::MiniTokio;
+///
+/// let mini_tokio = MiniTokio::new();
+/// mini_tokio.spawn(async {
+///     let listener = TcpListener::bind("127.0.0.1:6379").await.unwrap();
+///
+///     loop {
+///         let (socket, addr) = listener.accept().await.unwrap();
+///         println!("accepted {} ({:?})", addr, socket);
+///     }
+/// });
+/// mini_tokio.run();
+/// ```
+pub struct TcpListener {
+    io: mio::net::TcpListener,
+    registration: Registration,
+}
+
+impl TcpListener {
+    /// `addr` にバインドした `TcpListener` を生成する
+    ///
+    /// `addr` が複数のアドレスに解決された場合は、最初にバインドできたアドレスを使う。
+    ///
+    /// # Panics
+    ///
+    /// ランタイムの外から呼び出された場合はパニックする。
+    pub async fn bind(addr: impl ToSocketAddrs) -> io::Result<TcpListener> {
+        let io = each_addr(addr, mio::net::TcpListener::bind)?;
+        TcpListener::new(io)
+    }
+
+    /// 標準ライブラリの `TcpListener` から生成する
+    ///
+    /// # Panics
+    ///
+    /// ランタイムの外から呼び出された場合はパニックする。
+    pub fn from_std(listener: std::net::TcpListener) -> io::Result<TcpListener> {
+        listener.set_nonblocking(true)?;
+        TcpListener::new(mio::net::TcpListener::from_std(listener))
+    }
+
+    fn new(mut io: mio::net::TcpListener) -> io::Result<TcpListener> {
+        let registration = Registration::new(&mut io, Interest::READABLE)?;
+        Ok(TcpListener { io, registration })
+    }
+
+    /// 新しい接続を受け付ける
+    ///
+    /// 接続が来るまでタスクを `Pending` にして待つ。
+    pub async fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        let (io, addr) = self
+            .registration
+            .async_io(Direction::Read, || self.io.accept())
+            .await?;
+
+        Ok((TcpStream::new(io)?, addr))
+    }
+
+    /// バインドしているローカルアドレスを返す
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.io.local_addr()
+    }
+}
+
+impl fmt::Debug for TcpListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.io.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TcpListener;
+    use crate::net::TcpStream;
+    use crate::runtime::Builder;
+    use crate::time::timeout;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn concurrent_accepts_are_all_woken() {
+        // [MEMO]
+        // ワーカーが1つなら、2つのタスクが順に `accept` で `Pending` になってから接続が来る
+        let mini_tokio = Builder::new().worker_threads(1).build();
+
+        mini_tokio.block_on(async {
+            let listener = Arc::new(TcpListener::bind("127.0.0.1:0").await.unwrap());
+            let addr = listener.local_addr().unwrap();
+
+            let handles: Vec<_> = (0..2)
+                .map(|_| {
+                    let listener = listener.clone();
+                    crate::spawn(async move { listener.accept().await.map(|_| ()) })
+                })
+                .collect();
+
+            let _clients = (
+                TcpStream::connect(addr).await.unwrap(),
+                TcpStream::connect(addr).await.unwrap(),
+            );
+
+            for handle in handles {
+                let res = timeout(Duration::from_secs(5), handle).await;
+                res.expect("an accept was never woken").unwrap().unwrap();
+            }
+        });
+    }
+}
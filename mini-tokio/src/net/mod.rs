@@ -0,0 +1,37 @@
+//! I/O ドライバと連携する、ノンブロッキングな TCP ソケット
+//!
+//! ソケットの生成はランタイムの中（spawn したタスクの中）で行う必要がある。
+
+mod listener;
+pub use listener::TcpListener;
+
+mod stream;
+pub use stream::TcpStream;
+
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+
+/// `addr` を解決したアドレスを順に `f` に渡し、最初に成功した結果を返す
+fn each_addr<A, T>(addr: A, mut f: impl FnMut(SocketAddr) -> io::Result<T>) -> io::Result<T>
+where
+    A: ToSocketAddrs,
+{
+    let mut last_err = None;
+
+    for addr in addr.to_socket_addrs()? {
+        match f(addr) {
+            Ok(value) => return Ok(value),
+            Err(e) => last_err = Some(e),
+        }
+    }
+
+    Err(last_err.unwrap_or_else(no_addresses))
+}
+
+/// アドレスが1つも得られなかったときのエラー
+fn no_addresses() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "could not resolve to any address",
+    )
+}
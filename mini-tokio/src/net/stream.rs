@@ -0,0 +1,128 @@
+use super::no_addresses;
+use crate::io::{Direction, Registration};
+use mio::Interest;
+use std::fmt;
+use std::future;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, ToSocketAddrs};
+use std::task::{Context, Poll};
+
+/// ノンブロッキングな TCP 接続
+pub struct TcpStream {
+    io: mio::net::TcpStream,
+    registration: Registration,
+}
+
+impl TcpStream {
+    /// `addr` に接続する
+    ///
+    /// `addr` が複数のアドレスに解決された場合は、最初に接続できたアドレスを使う。
+    ///
+    /// # Panics
+    ///
+    /// ランタイムの外から呼び出された場合はパニックする。
+    pub async fn connect(addr: impl ToSocketAddrs) -> io::Result<TcpStream> {
+        let addrs: Vec<_> = addr.to_socket_addrs()?.collect();
+        let mut last_err = None;
+
+        for addr in addrs {
+            match TcpStream::connect_addr(addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_err = Some(e),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(no_addresses))
+    }
+
+    async fn connect_addr(addr: SocketAddr) -> io::Result<TcpStream> {
+        let stream = TcpStream::new(mio::net::TcpStream::connect(addr)?)?;
+
+        // [MEMO]
+        // ノンブロッキングな `connect` はすぐに返るので、書き込み可能になるまで待ってから接続の結果を確認する。
+        future::poll_fn(|cx| stream.registration.poll_ready(cx, Direction::Write)).await;
+
+        if let Some(e) = stream.io.take_error()? {
+            return Err(e);
+        }
+
+        Ok(stream)
+    }
+
+    /// 標準ライブラリの `TcpStream` から生成する
+    ///
+    /// # Panics
+    ///
+    /// ランタイムの外から呼び出された場合はパニックする。
+    pub fn from_std(stream: std::net::TcpStream) -> io::Result<TcpStream> {
+        stream.set_nonblocking(true)?;
+        TcpStream::new(mio::net::TcpStream::from_std(stream))
+    }
+
+    pub(crate) fn new(mut io: mio::net::TcpStream) -> io::Result<TcpStream> {
+        let registration = Registration::new(&mut io, Interest::READABLE | Interest::WRITABLE)?;
+        Ok(TcpStream { io, registration })
+    }
+
+    /// データを `buf` に読み込み、読み込んだバイト数を返す
+    ///
+    /// 0 が返った場合は、相手が接続の書き込み側を閉じている。
+    pub async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        future::poll_fn(|cx| self.poll_read(cx, buf)).await
+    }
+
+    /// `buf` のデータを書き込み、書き込んだバイト数を返す
+    pub async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        future::poll_fn(|cx| self.poll_write(cx, buf)).await
+    }
+
+    /// `buf` のデータを全て書き込む
+    pub async fn write_all(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf).await? {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                n => buf = &buf[n..],
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `read` の poll 版。自前の "future" を実装するときに使う
+    pub fn poll_read(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        self.registration
+            .poll_io(cx, Direction::Read, || (&self.io).read(buf))
+    }
+
+    /// `write` の poll 版。自前の "future" を実装するときに使う
+    pub fn poll_write(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.registration
+            .poll_io(cx, Direction::Write, || (&self.io).write(buf))
+    }
+
+    /// 接続の読み込み側・書き込み側、またはその両方を閉じる
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.io.shutdown(how)
+    }
+
+    /// Nagle アルゴリズムを無効にするかどうかを設定する
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        self.io.set_nodelay(nodelay)
+    }
+
+    /// 接続先のアドレスを返す
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.io.peer_addr()
+    }
+
+    /// ローカルアドレスを返す
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.io.local_addr()
+    }
+}
+
+impl fmt::Debug for TcpStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.io.fmt(f)
+    }
+}
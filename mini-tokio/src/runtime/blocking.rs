@@ -0,0 +1,146 @@
+use crate::runtime::Handle;
+use crate::task::Task;
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// `spawn_blocking` で spawn されたタスクを実行するスレッドプール
+///
+/// スレッドは空いているスレッドがないときに `max_threads` まで追加で起動し、
+/// `keep_alive` の間タスクが来なければ終了する。
+/// [MEMO]
+/// ブロッキングする処理をワーカーで実行すると、そのワーカーの他のタスクが進まなくなる。
+/// ワーカーとは別のスレッドで実行し、完了したら `JoinHandle` を待っているタスクを "waker" で起こす。
+pub(crate) struct BlockingPool {
+    inner: Mutex<Inner>,
+    condvar: Condvar,
+    max_threads: usize,
+    keep_alive: Duration,
+}
+
+struct Inner {
+    // 実行を待っているタスク
+    queue: VecDeque<Arc<Task>>,
+    // 起動しているスレッドの数
+    num_threads: usize,
+    // タスクを待って眠っているスレッドの数
+    num_idle: usize,
+    // 起こされたがまだタスクを取りに来ていないスレッドの数
+    // [MEMO]
+    // 条件変数は理由なく起きる（spurious wakeup）ことがあるため、起こされたのかどうかを数えて区別する。
+    num_notify: usize,
+    shutdown: bool,
+    // スレッド名に付ける連番
+    next_thread_id: usize,
+}
+
+impl BlockingPool {
+    pub(crate) fn new(max_threads: usize, keep_alive: Duration) -> BlockingPool {
+        BlockingPool {
+            inner: Mutex::new(Inner {
+                queue: VecDeque::new(),
+                num_threads: 0,
+                num_idle: 0,
+                num_notify: 0,
+                shutdown: false,
+                next_thread_id: 0,
+            }),
+            condvar: Condvar::new(),
+            max_threads,
+            keep_alive,
+        }
+    }
+
+    /// タスクをキューに入れ、眠っているスレッドを起こすか新しいスレッドを起動する
+    pub(crate) fn spawn(&self, handle: &Handle, task: Arc<Task>) {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.shutdown {
+            // 停止したランタイムのタスクは `Handle::shutdown` で破棄されている
+            return;
+        }
+
+        // [MEMO]
+        // 実行を待っているタスクも、眠っていない参加者として数える。
+        // 実行が終わるまでの間に、時刻が自動で進んでしまわないようにするため。
+        handle.inc_busy();
+        inner.queue.push_back(task);
+
+        if inner.num_idle > 0 {
+            inner.num_idle -= 1;
+            inner.num_notify += 1;
+            self.condvar.notify_one();
+        } else if inner.num_threads < self.max_threads {
+            // [MEMO]
+            // 上限に達している場合は、実行中のスレッドが空くまでキューで待つ
+            let id = inner.next_thread_id;
+            inner.next_thread_id += 1;
+            inner.num_threads += 1;
+
+            let handle = handle.clone();
+            thread::Builder::new()
+                .name(format!("mini-tokio-blocking-{}", id))
+                .spawn(move || handle.shared.blocking.run(&handle))
+                .expect("failed to spawn a blocking thread");
+        }
+    }
+
+    /// ブロッキングスレッドのループ
+    fn run(&self, handle: &Handle) {
+        // ブロッキングする処理の中からも `spawn` などを呼べるようにする
+        let _enter = handle.enter();
+
+        let mut inner = self.inner.lock().unwrap();
+
+        'run: loop {
+            while let Some(task) = inner.queue.pop_front() {
+                drop(inner);
+                task.poll();
+                handle.dec_busy();
+                inner = self.inner.lock().unwrap();
+            }
+
+            if inner.shutdown {
+                break;
+            }
+
+            // タスクが来るまで眠る。`keep_alive` の間に来なければスレッドを終了する
+            inner.num_idle += 1;
+
+            loop {
+                let (guard, res) = self.condvar.wait_timeout(inner, self.keep_alive).unwrap();
+                inner = guard;
+
+                if inner.num_notify > 0 {
+                    inner.num_notify -= 1;
+                    continue 'run;
+                }
+
+                if inner.shutdown || res.timed_out() {
+                    inner.num_idle -= 1;
+                    break 'run;
+                }
+            }
+        }
+
+        inner.num_threads -= 1;
+    }
+
+    /// 眠っているスレッドを全て終了させ、キューに残っているタスクを取り除く
+    ///
+    /// 実行中のスレッドは、そのタスクを終えてから終了する。
+    pub(crate) fn shutdown(&self) {
+        let queue = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.shutdown = true;
+            std::mem::take(&mut inner.queue)
+        };
+        self.condvar.notify_all();
+
+        // [MEMO]
+        // キューに残っているタスクは `Handle::shutdown` で破棄済みなので、ここでは参照を手放すだけでよい。
+        // `Task` の破棄は、プールのロックを解放してから行う。
+        drop(queue);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::runtime::MiniTokio;
+use std::time::Duration;
+
+/// `MiniTokio` の設定を組み立てるビルダー
+///
+/// ```no_run
+/// use mini_tokio::runtime::Builder;
+///
+/// let mini_tokio = Builder::new().worker_threads(4).build();
+/// mini_tokio.spawn(async {
+///     println!("hello from a worker thread");
+/// });
+/// mini_tokio.run();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Builder {
+    pub(super) worker_threads: usize,
+    pub(super) unhandled_panic: UnhandledPanic,
+    pub(super) max_blocking_threads: usize,
+    pub(super) thread_keep_alive: Duration,
+    pub(super) start_paused: bool,
+    pub(super) simulation: Option<u64>,
+}
+
+/// タスクがパニックしたときのランタイムの振る舞い
+///
+/// どちらの場合も、パニックしたタスクの `JoinHandle` はパニックのペイロードを含む `JoinError` を返す。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UnhandledPanic {
+    /// パニックしたタスクだけを終了させ、他のタスクはそのまま実行を続ける
+    Ignore,
+    /// パニックしたタスクがあれば、`MiniTokio::shutdown` と同様にランタイムを停止する
+    ShutdownRuntime,
+}
+
+impl Builder {
+    /// デフォルトの設定でビルダーを生成する
+    ///
+    /// デフォルトではワーカーは1つで、`run` を呼び出したスレッドだけでタスクを実行する。
+    pub fn new() -> Builder {
+        Builder {
+            worker_threads: 1,
+            unhandled_panic: UnhandledPanic::Ignore,
+            max_blocking_threads: 512,
+            thread_keep_alive: Duration::from_secs(10),
+            start_paused: false,
+            simulation: None,
+        }
+    }
+
+    /// タスクを実行するワーカースレッドの数を設定する
+    ///
+    /// `run` を呼び出したスレッドもワーカーの1つとして数える。
+    ///
+    /// # Panics
+    ///
+    /// `val` が 0 の場合はパニックする。
+    pub fn worker_threads(&mut self, val: usize) -> &mut Self {
+        assert!(val > 0, "worker threads cannot be set to 0");
+        self.worker_threads = val;
+        self
+    }
+
+    /// タスクがパニックしたときの振る舞いを設定する
+    ///
+    /// デフォルトは `UnhandledPanic::Ignore`。
+    pub fn unhandled_panic(&mut self, behavior: UnhandledPanic) -> &mut Self {
+        self.unhandled_panic = behavior;
+        self
+    }
+
+    /// `spawn_blocking` のためのスレッドの上限を設定する
+    ///
+    /// ブロッキングスレッドは必要になったときに起動され、上限に達した後の処理はキューで待たされる。
+    /// デフォルトは 512。
+    ///
+    /// # Panics
+    ///
+    /// `val` が 0 の場合はパニックする。
+    pub fn max_blocking_threads(&mut self, val: usize) -> &mut Self {
+        assert!(val > 0, "max blocking threads cannot be set to 0");
+        self.max_blocking_threads = val;
+        self
+    }
+
+    /// 処理がなくなったブロッキングスレッドを終了させるまでの時間を設定する
+    ///
+    /// デフォルトは 10 秒。
+    pub fn thread_keep_alive(&mut self, duration: Duration) -> &mut Self {
+        self.thread_keep_alive = duration;
+        self
+    }
+
+    /// ランタイムの時刻を一時停止した状態で始めるかどうかを設定する
+    ///
+    /// `true` にすると、`time::pause` を呼んだ状態でランタイムが始まる。デフォルトは `false`。
+    pub fn start_paused(&mut self, start_paused: bool) -> &mut Self {
+        self.start_paused = start_paused;
+        self
+    }
+
+    /// タスクを実行する順番を `seed` から作った乱数で決める、シミュレーション用のランタイムにする
+    ///
+    /// ワーカースレッドは起動せず、`run` や `block_on` を呼んだスレッドだけでタスクを実行する。
+    /// 実行を待っているタスクのうち、次にポーリングするものを乱数で選ぶので、
+    /// シードを変えながら実行すると様々な順番を試すことができ、失敗したシードを使えば同じ順番で再現できる。
+    /// `select!` が最初にポーリングする分岐も、同じ乱数で選ぶ。
+    ///
+    /// 時刻は一時停止した状態で始まり、実行できるタスクがなくなると次のタイマーの期限まで進む。
+    /// `worker_threads` と `start_paused` の設定は無視される。
+    /// 実行した順番は `MiniTokio::recorded_schedule` で取り出せる。
+    ///
+    /// [MEMO]
+    /// I/O イベント、`spawn_blocking` の完了、ランタイムの外のスレッドからの `wake` は、起きる時機までは決められない。
+    /// これらに依存する場合は、同じシードでも異なる順番で実行されることがある。
+    ///
+    /// ```no_run
+    /// use mini_tokio::runtime::Builder;
+    /// use std::panic::{self, AssertUnwindSafe};
+    ///
+    /// async fn scenario() {
+    ///     // 並行して動くタスクを spawn し、結果を `assert!` で確認する
+    /// }
+    ///
+    /// for seed in 0..1000 {
+    ///     let mini_tokio = Builder::new().simulation(seed).build();
+    ///
+    ///     let res = panic::catch_unwind(AssertUnwindSafe(|| mini_tokio.block_on(scenario())));
+    ///     if res.is_err() {
+    ///         // `simulation(seed)` で作り直せば、同じ順番で実行して再現できる
+    ///         println!("seed {} failed: {:?}", seed, mini_tokio.recorded_schedule());
+    ///         break;
+    ///     }
+    /// }
+    /// ```
+    pub fn simulation(&mut self, seed: u64) -> &mut Self {
+        self.simulation = Some(seed);
+        self
+    }
+
+    /// 設定した内容で `MiniTokio` を生成する
+    pub fn build(&self) -> MiniTokio {
+        MiniTokio::from_builder(self)
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Builder {
+        Builder::new()
+    }
+}
@@ -0,0 +1,557 @@
+use crate::io;
+use crate::sync::Notify;
+use crate::task::{JoinHandle, Task};
+use crate::time::{Clock, Timer, TimerKey};
+use crossbeam::deque::{Injector, Worker};
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::atomic::{self, AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+mod blocking;
+use blocking::BlockingPool;
+
+mod builder;
+pub use builder::{Builder, UnhandledPanic};
+
+pub(crate) mod rand;
+
+mod owned;
+use owned::OwnedTasks;
+
+mod park;
+
+mod simulation;
+use simulation::Simulation;
+
+mod worker;
+use worker::{Idle, WorkerLoop};
+
+/// タスクをスケジュールして実行する、最小限の非同期ランタイム
+pub struct MiniTokio {
+    handle: Handle,
+    // タスクを実行するワーカースレッドの数
+    worker_threads: usize,
+}
+
+/// ランタイムが所有する資源への参照
+///
+/// `run` の実行中はスレッドローカルに保存され、`Delay` などの "future" から `Handle::current` で取得できる。
+#[derive(Clone)]
+pub(crate) struct Handle {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    // ワーカーの外からスケジュールされたタスクを受け付けるグローバルキュー
+    // [MEMO]
+    // ワーカーの中からスケジュールされたタスクは、各ワーカーのローカルキューにプッシュされる。
+    // 1つのキューを全てのワーカーで奪い合わないようにするための仕組み。
+    injector: Injector<Arc<Task>>,
+    timer: Mutex<Timer>,
+    // タイマーや `Sleep` が使う時刻
+    clock: Clock,
+    // 眠っていない参加者の数
+    // [MEMO]
+    // 参加者は、ワーカー、`block_on` を呼んだスレッド、実行を待っているか実行中の `spawn_blocking` のタスク。
+    // 時刻が一時停止されている場合、全ての参加者が眠ったら次のタイマーの期限まで時刻を進める。
+    busy: AtomicUsize,
+    // epoll で I/O イベントを待つドライバ
+    io: io::Driver,
+    // `spawn_blocking` のタスクを実行するスレッドプール
+    blocking: BlockingPool,
+    idle: Idle,
+    // まだ完了していないタスク
+    // [MEMO]
+    // `MiniTokio` 自身もタスクを送信できるため、キューが閉じるのを待つ方法では `run` が返らない。
+    // そのため、完了していないタスクを数えて、全て完了したら `run` を返すようにしている。
+    owned: OwnedTasks,
+    // タスクの識別子を払い出すカウンタ
+    // [MEMO]
+    // ランタイムごとに 1 から数えるので、シミュレーションでは同じシードなら同じタスクに同じ識別子が付く。
+    next_task_id: AtomicU64,
+    // `Builder::simulation` で作られた場合の、タスクを実行する順番を決めるスケジューラ
+    simulation: Option<Simulation>,
+    // `shutdown` が呼ばれたかどうか
+    shutdown: AtomicBool,
+    // タスクがパニックしたときの振る舞い
+    unhandled_panic: UnhandledPanic,
+}
+
+thread_local! {
+    // 現在のスレッドで実行中のランタイム
+    static CURRENT: RefCell<Option<Handle>> = const { RefCell::new(None) };
+}
+
+impl MiniTokio {
+    /// mini-tokio インスタンスを初期化する
+    ///
+    /// ワーカーの数などを設定したい場合は `Builder` を利用する。
+    pub fn new() -> MiniTokio {
+        Builder::new().build()
+    }
+
+    fn from_builder(builder: &Builder) -> MiniTokio {
+        let handle = Handle {
+            shared: Arc::new(Shared {
+                injector: Injector::new(),
+                timer: Mutex::new(Timer::new()),
+                // シミュレーションでは、時刻を一時停止した状態で始める
+                clock: Clock::new(builder.start_paused || builder.simulation.is_some()),
+                busy: AtomicUsize::new(0),
+                io: io::Driver::new().expect("failed to create the I/O driver"),
+                blocking: BlockingPool::new(
+                    builder.max_blocking_threads,
+                    builder.thread_keep_alive,
+                ),
+                idle: Idle::new(),
+                owned: OwnedTasks::new(),
+                next_task_id: AtomicU64::new(1),
+                simulation: builder.simulation.map(Simulation::new),
+                shutdown: AtomicBool::new(false),
+                unhandled_panic: builder.unhandled_panic,
+            }),
+        };
+
+        MiniTokio {
+            handle,
+            worker_threads: builder.worker_threads,
+        }
+    }
+
+    /// スケジュールされたタスクを実行する
+    ///
+    /// 複数のワーカーが設定されている場合は、`run` を呼び出したスレッドに加えてワーカースレッドを起動する。
+    /// 各ワーカーは自分のローカルキューを持ち、空になったらグローバルキューや他のワーカーのキューからタスクを盗む。
+    ///
+    /// spawn された全てのタスクが完了するか、`shutdown` が呼ばれると返る。
+    ///
+    /// `Builder::simulation` で作られた場合は、ワーカースレッドを起動せず、呼び出したスレッドだけでタスクを実行する。
+    pub fn run(&self) {
+        if self.handle.simulation().is_some() {
+            return simulation::run(&self.handle);
+        }
+
+        let done = || self.handle.is_done();
+        let queues: Vec<_> = (0..self.worker_threads)
+            .map(|_| Worker::new_fifo())
+            .collect();
+        let stealers: Vec<_> = queues.iter().map(Worker::stealer).collect();
+        let stealers = &stealers[..];
+
+        // [MEMO]
+        // `thread::scope` で起動したスレッドは、スコープを抜ける前に必ず join される。
+        // そのため、ワーカースレッドから `self` を借用することができる。
+        thread::scope(|scope| {
+            let mut queues = queues.into_iter().enumerate();
+            let (_, first) = queues.next().unwrap();
+
+            for (i, queue) in queues {
+                thread::Builder::new()
+                    .name(format!("mini-tokio-worker-{}", i))
+                    .spawn_scoped(scope, move || {
+                        WorkerLoop::new(&self.handle, i, queue, stealers, &done).run()
+                    })
+                    .expect("failed to spawn a worker thread");
+            }
+
+            WorkerLoop::new(&self.handle, 0, first, stealers, &done).run();
+        });
+    }
+
+    /// `future` を完了まで実行し、その出力を返す
+    ///
+    /// `future` は呼び出したスレッドでポーリングされ、"waker" が呼ばれるまでスレッドは眠る。
+    /// その間もワーカースレッドが起動しており、spawn されたタスクは並行して実行される。
+    /// `future` が完了した時点で返り、完了していないタスクは次の `run` や `block_on` で続きが実行される。
+    ///
+    /// # Panics
+    ///
+    /// ランタイムのコンテキスト（タスクの中など）から呼び出された場合はパニックする。
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use mini_tokio::MiniTokio;
+    ///
+    /// let mini_tokio = MiniTokio::new();
+    ///
+    /// let value = mini_tokio.block_on(async {
+    ///     let handle = mini_tokio::spawn(async { 1 + 2 });
+    ///     handle.await.unwrap()
+    /// });
+    ///
+    /// assert_eq!(value, 3);
+    /// ```
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        // [MEMO]
+        // ワーカーの中でスレッドを眠らせると、そのワーカーのタスクが実行されなくなりデッドロックする。
+        // tokio と同様に、ランタイムの中からの呼び出しは禁止している。
+        let in_runtime = CURRENT.with(|current| current.borrow().is_some());
+        assert!(
+            !in_runtime,
+            "cannot start a runtime from within a runtime; \
+             `block_on` must not be called from a task"
+        );
+
+        if self.handle.simulation().is_some() {
+            return simulation::block_on(&self.handle, future);
+        }
+
+        // `block_on` のワーカーは、spawn されたタスクが全て完了しても `future` が完了するまでは終了しない
+        let stop = AtomicBool::new(false);
+        let done = || self.handle.is_shutdown() || stop.load(Ordering::SeqCst);
+        let queues: Vec<_> = (0..self.worker_threads)
+            .map(|_| Worker::new_fifo())
+            .collect();
+        let stealers: Vec<_> = queues.iter().map(Worker::stealer).collect();
+        let stealers = &stealers[..];
+
+        thread::scope(|scope| {
+            for (i, queue) in queues.into_iter().enumerate() {
+                thread::Builder::new()
+                    .name(format!("mini-tokio-worker-{}", i))
+                    .spawn_scoped(scope, move || {
+                        WorkerLoop::new(&self.handle, i, queue, stealers, &done).run()
+                    })
+                    .expect("failed to spawn a worker thread");
+            }
+
+            // [MEMO]
+            // `future` がパニックした場合でもワーカーを終了させないと、スコープの終わりで join できずに止まってしまう。
+            // そのため、ワーカーの停止は `Drop` で行う。
+            let _stop = StopWorkers {
+                handle: &self.handle,
+                stop: &stop,
+            };
+
+            let _enter = self.handle.enter();
+            park::block_on(&self.handle, future)
+        })
+    }
+
+    /// ランタイムを停止し、完了していないタスクを全て破棄する
+    ///
+    /// 実行中の `run` は、各ワーカーがポーリング中のタスクを終えた時点で返る。
+    /// 破棄されたタスクの "future" はその場で drop され、`JoinHandle` はキャンセルを表す `JoinError` を返す。
+    /// `shutdown` の後に spawn されたタスクも、実行されずにすぐ破棄される。
+    pub fn shutdown(&self) {
+        self.handle.shutdown();
+    }
+
+    /// シミュレーションでポーリングしたタスクの識別子を、ポーリングした順に返す
+    ///
+    /// `block_on` に渡した "future" は 0 として記録される。
+    /// `Builder::simulation` で作られていない場合は `None` を返す。
+    ///
+    /// [MEMO]
+    /// 同じシードで実行した2回の結果を比べると、実行の順番が再現できているかを確認できる。
+    pub fn recorded_schedule(&self) -> Option<Vec<u64>> {
+        self.handle.simulation().map(Simulation::schedule)
+    }
+
+    /// mini-tokio のインスタンスに "future" を渡す
+    ///
+    /// 与えられる "future" は `Task` によってラップされ、`スケジュール` キューにプッシュされる。
+    /// `run` が呼び出されたときに "future" が実行される。
+    /// 戻り値の `JoinHandle` を `.await` すると、"future" の出力を受け取ることができる
+    pub fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        Task::spawn(future, &self.handle)
+    }
+}
+
+impl Default for MiniTokio {
+    fn default() -> MiniTokio {
+        MiniTokio::new()
+    }
+}
+
+impl Drop for MiniTokio {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+impl Handle {
+    /// 現在のスレッドで実行中のランタイムの `Handle` を返す
+    ///
+    /// ランタイムの外から呼び出された場合はパニックする。
+    pub(crate) fn current() -> Handle {
+        CURRENT.with(|current| {
+            current
+                .borrow()
+                .clone()
+                .expect("must be called from the context of a mini-tokio runtime")
+        })
+    }
+
+    /// 現在のスレッドで実行中のランタイムがあれば、その `Handle` を返す
+    pub(crate) fn try_current() -> Option<Handle> {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+
+    /// `Builder::simulation` で作られた場合は、タスクを実行する順番を決めるスケジューラを返す
+    pub(crate) fn simulation(&self) -> Option<&Simulation> {
+        self.shared.simulation.as_ref()
+    }
+
+    /// 新しいタスクの識別子を払い出す
+    pub(crate) fn next_task_id(&self) -> u64 {
+        self.shared.next_task_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub(crate) fn clock(&self) -> &Clock {
+        &self.shared.clock
+    }
+
+    /// `when` に通知される `Notify` をタイマーに登録し、取り除くためのキーを返す
+    ///
+    /// 登録したタイマーが最も早い期限になった場合は、眠っているワーカーを起こす。
+    /// [MEMO]
+    /// 眠っているワーカーは、眠る前に確認した期限までしか起きない。
+    /// `block_on` の "future" のようにワーカーの外で登録されたタイマーは、起こさないと期限を過ぎても処理されない。
+    pub(crate) fn register_timer(&self, when: Instant, notify: Arc<Notify>) -> TimerKey {
+        let (key, is_earliest) = {
+            let mut timer = self.shared.timer.lock().unwrap();
+            let is_earliest = timer.next_deadline().is_none_or(|next| when < next);
+            (timer.register(when, notify), is_earliest)
+        };
+
+        if is_earliest {
+            self.unpark_one();
+        }
+
+        key
+    }
+
+    /// まだ期限を迎えていないタイマーを取り除く
+    ///
+    /// [MEMO]
+    /// 取り除いたタイマーで眠っているワーカーを起こすことはしない。
+    /// ワーカーはその期限に一度起きるが、期限を過ぎたタイマーがないことを確認して眠り直すだけである。
+    pub(crate) fn deregister_timer(&self, key: TimerKey) {
+        // `Notify` の破棄はロックを解放してから行う
+        let notify = self.shared.timer.lock().unwrap().deregister(key);
+        drop(notify);
+    }
+
+    pub(crate) fn io(&self) -> &io::Driver {
+        &self.shared.io
+    }
+
+    /// タスクを実行キューにプッシュし、眠っているワーカーがいれば起こす
+    ///
+    /// このランタイムのワーカーから呼ばれた場合はそのワーカーのローカルキューに、
+    /// それ以外の場合はグローバルキューにプッシュする。
+    /// シミュレーションの場合は、シミュレーションの実行待ちに加える。
+    ///
+    /// ランタイムが停止していてタスクを受け付けなかった場合は `false` を返す。
+    pub(crate) fn schedule(&self, task: Arc<Task>) -> bool {
+        // 停止したランタイムにはタスクを積まない
+        if self.is_shutdown() {
+            return false;
+        }
+
+        if let Some(simulation) = self.simulation() {
+            simulation.push(task);
+        } else if let Err(task) = worker::push_local(self, task) {
+            self.shared.injector.push(task);
+        }
+
+        self.unpark_one();
+        true
+    }
+
+    /// タスクをブロッキングスレッドで実行する
+    pub(crate) fn spawn_blocking(&self, task: Arc<Task>) {
+        self.shared.blocking.spawn(self, task);
+    }
+
+    /// 新しいタスクをランタイムの管理下に置く。ランタイムが停止している場合は `false` を返す
+    pub(crate) fn bind(&self, task: &Arc<Task>) -> bool {
+        self.shared.owned.bind(task)
+    }
+
+    /// 完了したタスクを管理下から外す
+    ///
+    /// 最後のタスクが完了した場合は、眠っているワーカーを全て起こして `run` を終了させる。
+    pub(crate) fn release(&self, task: &Task) {
+        if self.shared.owned.remove(task) {
+            self.unpark_all();
+        }
+    }
+
+    /// ランタイムを停止する。詳細は `MiniTokio::shutdown` を参照
+    pub(crate) fn shutdown(&self) {
+        let shared = &self.shared;
+
+        shared.shutdown.store(true, Ordering::SeqCst);
+        self.unpark_all();
+
+        for task in shared.owned.close() {
+            task.shutdown();
+        }
+
+        // キューに残っている `Task` への参照と、タイマーも破棄する
+        // [MEMO]
+        // `Task` は `Handle` を通して `Shared` を参照しているため、ここで破棄しないと循環参照になりメモリが解放されない。
+        while !shared.injector.steal().is_empty() {}
+        if let Some(simulation) = &shared.simulation {
+            simulation.clear();
+        }
+        let timers = shared.timer.lock().unwrap().clear();
+        drop(timers);
+        shared.io.shutdown();
+        shared.blocking.shutdown();
+    }
+
+    /// タスクがパニックしたときに呼ばれ、設定に応じてランタイムを停止する
+    pub(crate) fn unhandled_panic(&self) {
+        match self.shared.unhandled_panic {
+            UnhandledPanic::Ignore => {}
+            UnhandledPanic::ShutdownRuntime => self.shutdown(),
+        }
+    }
+
+    pub(crate) fn is_shutdown(&self) -> bool {
+        self.shared.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// ワーカーが終了してよいかどうか
+    fn is_done(&self) -> bool {
+        self.is_shutdown() || self.shared.owned.is_empty()
+    }
+
+    /// 眠っているワーカーを1つ起こす
+    ///
+    /// 条件変数で眠っているワーカーがいなければ、epoll で待っているワーカーを起こす。
+    fn unpark_one(&self) {
+        if !self.shared.idle.notify_one() {
+            self.shared.io.unpark();
+        }
+    }
+
+    /// 眠っているワーカーを全て起こす
+    pub(crate) fn unpark_all(&self) {
+        self.shared.idle.notify_all();
+        self.shared.io.unpark();
+    }
+
+    fn ptr_eq(&self, other: &Handle) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+
+    /// 現在のスレッドに `self` を設定する。戻り値が破棄されると元に戻る
+    fn enter(&self) -> EnterGuard {
+        let prev = CURRENT.with(|current| current.borrow_mut().replace(self.clone()));
+        EnterGuard { prev }
+    }
+
+    /// `deadline` より早い期限のタイマーが登録されていれば `true` を返す
+    ///
+    /// `deadline` が `None` の場合は、タイマーが1つでも登録されていれば `true` を返す。
+    fn has_earlier_timer(&self, deadline: Option<Instant>) -> bool {
+        let next = self.shared.timer.lock().unwrap().next_deadline();
+
+        match (next, deadline) {
+            (Some(next), Some(deadline)) => next < deadline,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    /// 期限を過ぎたタイマーに通知し、次のタイマーの期限を返す
+    pub(crate) fn process_timers(&self) -> Option<Instant> {
+        let (expired, next) = {
+            let mut timer = self.shared.timer.lock().unwrap();
+            let expired = timer.process(self.shared.clock.now());
+            (expired, timer.next_deadline())
+        };
+
+        // 通知はタスクをスケジュールキューに送信するので、タイマーのロックを解放してから行う
+        // [MEMO]
+        // `Delay` がまだ待ち始めていなくても、`notify_one` は許可として保存されるので通知は失われない。
+        for notify in expired {
+            notify.notify_one();
+        }
+
+        next
+    }
+
+    /// 眠っていない参加者の数を1つ増やす
+    fn inc_busy(&self) {
+        self.shared.busy.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// 眠っていない参加者の数を1つ減らす
+    ///
+    /// 全ての参加者が眠り、時刻が一時停止されている場合は、ワーカーを起こして時刻を進めさせる。
+    /// ワーカー自身が眠るときは、起こさずに自分で `auto_advance` を呼ぶ。
+    fn dec_busy(&self) {
+        if self.shared.busy.fetch_sub(1, Ordering::SeqCst) == 1 && self.shared.clock.is_paused() {
+            self.unpark_one();
+        }
+    }
+
+    /// 時刻が一時停止されていて、全ての参加者が眠っていれば、次のタイマーの期限まで時刻を進める
+    ///
+    /// 時刻を進めた場合は `true` を返す。`has_work` には、実行を待っているタスクがあるかどうかを渡す。
+    fn auto_advance(&self, has_work: impl Fn() -> bool) -> bool {
+        if !self.shared.clock.is_paused() || has_work() {
+            return false;
+        }
+
+        // [MEMO]
+        // キューを確認してから参加者の数を確認する。
+        // ワーカーはタスクを取り出す前に参加者の数を増やすので、タスクがキューから取り出されていれば、
+        // 取り出したワーカーは必ず数えられている。
+        atomic::fence(Ordering::SeqCst);
+        if self.shared.busy.load(Ordering::SeqCst) != 0 {
+            return false;
+        }
+
+        match self.shared.timer.lock().unwrap().next_deadline() {
+            Some(next) => {
+                self.shared.clock.advance_to(next);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// `auto_advance` で時刻を進められる状態かどうか
+    fn can_auto_advance(&self) -> bool {
+        self.shared.clock.is_paused()
+            && self.shared.busy.load(Ordering::SeqCst) == 0
+            && self.shared.timer.lock().unwrap().next_deadline().is_some()
+    }
+}
+
+/// 破棄されると `block_on` のワーカーを終了させる
+struct StopWorkers<'a> {
+    handle: &'a Handle,
+    stop: &'a AtomicBool,
+}
+
+impl Drop for StopWorkers<'_> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        self.handle.unpark_all();
+    }
+}
+
+struct EnterGuard {
+    prev: Option<Handle>,
+}
+
+impl Drop for EnterGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.prev.take());
+    }
+}
@@ -0,0 +1,66 @@
+use crate::task::Task;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// ランタイムが所有している、まだ完了していないタスクの一覧
+///
+/// `run` は一覧が空になったら返る。`shutdown` では一覧に残っているタスクをまとめて破棄する。
+pub(crate) struct OwnedTasks {
+    inner: Mutex<Inner>,
+    // `inner` のロックを取らずに確認できるよう、タスクの数を別に保持する
+    len: AtomicUsize,
+}
+
+struct Inner {
+    tasks: HashMap<u64, Arc<Task>>,
+    // `close` が呼ばれた後は新しいタスクを受け付けない
+    closed: bool,
+}
+
+impl OwnedTasks {
+    pub(crate) fn new() -> OwnedTasks {
+        OwnedTasks {
+            inner: Mutex::new(Inner {
+                tasks: HashMap::new(),
+                closed: false,
+            }),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// タスクを一覧に追加する。すでに `close` されていた場合は `false` を返す
+    pub(crate) fn bind(&self, task: &Arc<Task>) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.closed {
+            return false;
+        }
+
+        inner.tasks.insert(task.id(), task.clone());
+        self.len.fetch_add(1, Ordering::SeqCst);
+        true
+    }
+
+    /// 完了したタスクを一覧から取り除く。一覧が空になった場合は `true` を返す
+    pub(crate) fn remove(&self, task: &Task) -> bool {
+        let removed = self.inner.lock().unwrap().tasks.remove(&task.id());
+
+        match removed {
+            Some(_) => self.len.fetch_sub(1, Ordering::SeqCst) == 1,
+            None => false,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len.load(Ordering::SeqCst) == 0
+    }
+
+    /// 新しいタスクの受け付けを止め、一覧に残っているタスクを全て取り出す
+    pub(crate) fn close(&self) -> Vec<Arc<Task>> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.closed = true;
+        self.len.store(0, Ordering::SeqCst);
+
+        inner.tasks.drain().map(|(_, task)| task).collect()
+    }
+}
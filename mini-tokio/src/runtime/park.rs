@@ -0,0 +1,93 @@
+use crate::runtime::Handle;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+// `block_on` を呼んだスレッドの状態
+// ポーリングしている
+const RUNNING: usize = 0;
+// ポーリング中に "waker" が呼ばれた
+const NOTIFIED: usize = 1;
+// 眠っている
+const IDLE: usize = 2;
+
+/// `future` を現在のスレッドでポーリングし、完了するまで待つ
+///
+/// `Pending` が返るたびにスレッドを眠らせ、"waker" が呼ばれたら起きて再度ポーリングする。
+pub(super) fn block_on<F: Future>(handle: &Handle, future: F) -> F::Output {
+    let mut future = pin!(future);
+
+    let thread_waker = Arc::new(ThreadWaker {
+        thread: thread::current(),
+        handle: handle.clone(),
+        state: AtomicUsize::new(RUNNING),
+    });
+    let waker = Waker::from(thread_waker.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    // [MEMO]
+    // `block_on` を呼んだスレッドも、眠っていない参加者として数える。
+    // 数えないと、"future" をポーリングしている間に時刻が自動で進んでしまう。
+    handle.inc_busy();
+    let _busy = BusyGuard(handle);
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+
+        // ポーリング中に "waker" が呼ばれていなければ眠る
+        if thread_waker
+            .state
+            .compare_exchange(RUNNING, IDLE, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            handle.dec_busy();
+
+            // [MEMO]
+            // `unpark` が `park` より先に呼ばれた場合、次の `park` はすぐに返るので起こし忘れは起きない。
+            // 逆に、"waker" が呼ばれていなくても `park` が返ることがある（spurious wakeup）ため、
+            // 状態が変わるまで眠り直す。
+            while thread_waker.state.load(Ordering::SeqCst) == IDLE {
+                thread::park();
+            }
+        }
+
+        thread_waker.state.store(RUNNING, Ordering::SeqCst);
+    }
+}
+
+/// 呼び出されると、`block_on` で眠っているスレッドを起こす "waker"
+struct ThreadWaker {
+    thread: Thread,
+    handle: Handle,
+    state: AtomicUsize,
+}
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if self.state.swap(NOTIFIED, Ordering::SeqCst) == IDLE {
+            // [MEMO]
+            // 起こす側が参加者の数を増やす。
+            // 起こされたスレッドが増やすまでの間に、時刻が自動で進んでしまわないようにするため。
+            self.handle.inc_busy();
+            self.thread.unpark();
+        }
+    }
+}
+
+/// 破棄されると、眠っていない参加者の数を1つ減らす
+struct BusyGuard<'a>(&'a Handle);
+
+impl Drop for BusyGuard<'_> {
+    fn drop(&mut self) {
+        self.0.dec_busy();
+    }
+}
@@ -0,0 +1,63 @@
+use crate::runtime::Handle;
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::thread;
+
+/// 軽量な疑似乱数生成器 (xorshift64*)
+///
+/// ワーカーがタスクを盗む相手を選ぶなど、暗号学的な品質が不要な場面で利用する。
+pub(crate) struct FastRand {
+    state: u64,
+}
+
+impl FastRand {
+    /// `seed` から乱数生成器を初期化する
+    pub(crate) fn new(seed: u64) -> FastRand {
+        // xorshift は状態が 0 だと 0 しか返さなくなるため、0 を避ける
+        FastRand {
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    /// `value` とプロセスごとのランダムな鍵から種を作って初期化する
+    pub(crate) fn from_entropy(value: impl Hash) -> FastRand {
+        FastRand::new(RandomState::new().hash_one(value))
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// `0..n` の範囲の乱数を返す
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+thread_local! {
+    static THREAD_RNG: RefCell<FastRand> = RefCell::new(FastRand::from_entropy(thread::current().id()));
+}
+
+/// スレッドごとの乱数生成器から、`0..n` の範囲の乱数を返す
+///
+/// `select!` が最初にポーリングする分岐を選ぶときなど、ランタイムの外からも使う。
+/// シミュレーションのランタイムの中では、結果を再現できるようにシミュレーションの乱数を使う。
+pub(crate) fn thread_rng_n(n: u32) -> u32 {
+    if let Some(handle) = Handle::try_current() {
+        if let Some(simulation) = handle.simulation() {
+            return simulation.rand_n(n);
+        }
+    }
+
+    THREAD_RNG.with(|rng| rng.borrow_mut().below(n as usize) as u32)
+}
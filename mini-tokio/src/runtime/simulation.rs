@@ -0,0 +1,275 @@
+use crate::runtime::rand::FastRand;
+use crate::runtime::Handle;
+use crate::task::Task;
+use std::future::Future;
+use std::mem;
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+// `block_on` に渡された "future" を、実行した順番に記録するときの識別子
+// [MEMO]
+// タスクの識別子は 1 から払い出されるので、0 と重なることはない。
+const MAIN_ID: u64 = 0;
+
+/// 次にポーリングするものを、シードから作った乱数で選ぶスケジューラ
+///
+/// `Builder::simulation` で作ったランタイムは、ワーカースレッドを起動せずに、
+/// `run` や `block_on` を呼んだスレッドだけでタスクを実行する。
+/// 同じシードであれば、実行を待っているタスクが同じなら同じタスクが選ばれるので、実行の順番を再現できる。
+pub(crate) struct Simulation {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    rand: FastRand,
+    // 実行を待っているタスク
+    // [MEMO]
+    // 乱数で選んだ位置から取り出すので、キューではなく `Vec` で持つ。
+    queue: Vec<Arc<Task>>,
+    // ポーリングした順に並べた識別子
+    schedule: Vec<u64>,
+}
+
+/// 次にポーリングするもの
+enum Next {
+    Task(Arc<Task>),
+    // `block_on` に渡された "future"
+    Main,
+}
+
+impl Simulation {
+    pub(crate) fn new(seed: u64) -> Simulation {
+        Simulation {
+            inner: Mutex::new(Inner {
+                rand: FastRand::new(seed),
+                queue: Vec::new(),
+                schedule: Vec::new(),
+            }),
+        }
+    }
+
+    /// タスクを実行待ちに加える
+    pub(crate) fn push(&self, task: Arc<Task>) {
+        self.inner.lock().unwrap().queue.push(task);
+    }
+
+    /// シードから作った乱数で、`0..n` の範囲の乱数を返す
+    pub(crate) fn rand_n(&self, n: u32) -> u32 {
+        self.inner.lock().unwrap().rand.below(n as usize) as u32
+    }
+
+    /// これまでにポーリングしたタスクの識別子を、ポーリングした順に返す
+    pub(crate) fn schedule(&self) -> Vec<u64> {
+        self.inner.lock().unwrap().schedule.clone()
+    }
+
+    /// 実行待ちのタスクを全て破棄する
+    pub(crate) fn clear(&self) {
+        // `Task` の破棄はロックを解放してから行う
+        let queue = mem::take(&mut self.inner.lock().unwrap().queue);
+        drop(queue);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.lock().unwrap().queue.is_empty()
+    }
+
+    /// 実行待ちのタスクと、起こされた `block_on` の "future" から、次にポーリングするものを乱数で選ぶ
+    fn next(&self, main_woken: bool) -> Option<Next> {
+        let mut inner = self.inner.lock().unwrap();
+        let len = inner.queue.len();
+
+        if len == 0 && !main_woken {
+            return None;
+        }
+
+        let i = inner.rand.below(len + main_woken as usize);
+        if i == len {
+            inner.schedule.push(MAIN_ID);
+            return Some(Next::Main);
+        }
+
+        let task = inner.queue.swap_remove(i);
+        inner.schedule.push(task.id());
+        Some(Next::Task(task))
+    }
+}
+
+/// spawn された全てのタスクが完了するか、ランタイムが停止するまでタスクを実行する
+pub(super) fn run(handle: &Handle) {
+    run_until(handle, None);
+}
+
+/// `future` が完了するまでタスクを実行し、その出力を返す
+pub(super) fn block_on<F: Future>(handle: &Handle, future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut output = None;
+
+    run_until(
+        handle,
+        Some(&mut |cx: &mut Context<'_>| match future.as_mut().poll(cx) {
+            Poll::Ready(value) => {
+                output = Some(value);
+                true
+            }
+            Poll::Pending => false,
+        }),
+    );
+
+    output.unwrap()
+}
+
+/// 現在のスレッドでタスクを1つずつ選んでポーリングする
+///
+/// `main` が渡された場合は、`block_on` の "future" をポーリングし、完了したら `true` を返す関数として扱う。
+/// `main` が完了するまで返らない。`None` の場合は、全てのタスクが完了するか、ランタイムが停止したら返る。
+fn run_until(handle: &Handle, mut main: Option<&mut dyn FnMut(&mut Context<'_>) -> bool>) {
+    let simulation = handle
+        .simulation()
+        .expect("the runtime is not a simulation");
+    let _enter = handle.enter();
+
+    let has_main = main.is_some();
+    let main_waker = Arc::new(MainWaker {
+        woken: AtomicBool::new(true),
+        handle: handle.clone(),
+    });
+    let waker = Waker::from(main_waker.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let main_woken = || has_main && main_waker.woken.load(Ordering::SeqCst);
+    let done = || !has_main && handle.is_done();
+
+    while !done() {
+        // [MEMO]
+        // 時刻が一時停止されている間は、`time::advance` か下の `auto_advance` でしか時刻が進まない。
+        // そのため、ここでタイマーを処理しても実行の順番は変わらない。
+        handle.process_timers();
+
+        match simulation.next(main_woken()) {
+            Some(Next::Main) => {
+                // ポーリング中に起こされた場合に備えて、ポーリングする前に戻しておく
+                main_waker.woken.store(false, Ordering::SeqCst);
+
+                if let Some(main) = main.as_mut() {
+                    if main(&mut cx) {
+                        return;
+                    }
+                }
+            }
+            Some(Next::Task(task)) => task.poll(),
+            None => {
+                // 実行できるものがないので、時刻を進めるか、ランタイムの外からの `wake` を待つ
+                // [MEMO]
+                // このスレッドは参加者として数えていないので、参加者は実行を待っているか実行中の `spawn_blocking` のタスクだけである。
+                let has_work = || !simulation.is_empty() || main_woken() || done();
+                if handle.auto_advance(has_work) {
+                    continue;
+                }
+
+                let next_deadline = handle.process_timers();
+                let has_work = || {
+                    has_work()
+                        || handle.has_earlier_timer(next_deadline)
+                        || handle.can_auto_advance()
+                };
+                let park_deadline = handle.clock().park_deadline(next_deadline);
+
+                if !handle.io().try_park(park_deadline, has_work) {
+                    handle.shared.idle.park(park_deadline, has_work);
+                }
+            }
+        }
+    }
+}
+
+/// 呼び出されると、`block_on` の "future" をポーリングの候補に加える "waker"
+struct MainWaker {
+    woken: AtomicBool,
+    handle: Handle,
+}
+
+impl Wake for MainWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::SeqCst);
+        // ランタイムの外のスレッドから呼ばれた場合に備えて、眠っているスレッドを起こす
+        self.handle.unpark_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::Builder;
+    use crate::sync::mpsc;
+    use std::future::poll_fn;
+    use std::task::Poll;
+
+    // 一度だけ `Pending` を返して、他のタスクに順番を譲る
+    async fn yield_now() {
+        let mut yielded = false;
+        poll_fn(|cx| {
+            if yielded {
+                return Poll::Ready(());
+            }
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// 複数のタスクが1つのチャネルに送信するシナリオを実行し、実行した順番と受信した順番を返す
+    fn run_scenario(seed: u64) -> (Vec<u64>, Vec<u64>) {
+        let mini_tokio = Builder::new().simulation(seed).build();
+
+        let received = mini_tokio.block_on(async {
+            let (tx, mut rx) = mpsc::channel(16);
+
+            for i in 0..8 {
+                let tx = tx.clone();
+                crate::spawn(async move {
+                    for round in 0..3 {
+                        yield_now().await;
+                        tx.send(i * 10 + round).await.unwrap();
+                    }
+                });
+            }
+            drop(tx);
+
+            let mut received = Vec::new();
+            while let Some(value) = rx.recv().await {
+                received.push(value);
+            }
+            received
+        });
+
+        (mini_tokio.recorded_schedule().unwrap(), received)
+    }
+
+    #[test]
+    fn same_seed_replays_same_schedule() {
+        for seed in 0..20 {
+            assert_eq!(run_scenario(seed), run_scenario(seed), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_schedules() {
+        let (schedule, received) = run_scenario(1);
+        let (other_schedule, other_received) = run_scenario(2);
+
+        assert_ne!(schedule, other_schedule);
+        assert_ne!(received, other_received);
+    }
+
+    #[test]
+    fn not_recorded_without_simulation() {
+        assert!(Builder::new().build().recorded_schedule().is_none());
+    }
+}
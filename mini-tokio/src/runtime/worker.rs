@@ -0,0 +1,269 @@
+use crate::runtime::rand::FastRand;
+use crate::runtime::Handle;
+use crate::task::Task;
+use crossbeam::deque::{Steal, Stealer, Worker};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{self, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+
+// ローカルキューばかりを処理してグローバルキューのタスクが飢餓状態にならないよう、
+// この回数に1回はグローバルキューを先に確認する
+const GLOBAL_QUEUE_INTERVAL: u32 = 61;
+
+thread_local! {
+    // 現在のスレッドで動作しているワーカーのローカルキュー
+    static CURRENT_QUEUE: RefCell<Option<LocalQueue>> = const { RefCell::new(None) };
+}
+
+struct LocalQueue {
+    handle: Handle,
+    queue: Rc<Worker<Arc<Task>>>,
+}
+
+/// 現在のスレッドが `handle` のワーカーであれば、そのローカルキューに `task` をプッシュする
+///
+/// ワーカー以外のスレッドから呼ばれた場合は `task` をそのまま返す。
+pub(crate) fn push_local(handle: &Handle, task: Arc<Task>) -> Result<(), Arc<Task>> {
+    CURRENT_QUEUE.with(|current| match &*current.borrow() {
+        Some(local) if local.handle.ptr_eq(handle) => {
+            local.queue.push(task);
+            Ok(())
+        }
+        _ => Err(task),
+    })
+}
+
+/// 1つのワーカーが、自分のローカルキュー・グローバルキュー・他のワーカーのキューからタスクを探して実行する
+pub(crate) struct WorkerLoop<'a> {
+    handle: &'a Handle,
+    index: usize,
+    queue: Rc<Worker<Arc<Task>>>,
+    // 全てのワーカーのローカルキューから盗むためのハンドル（自分のものも含む）
+    stealers: &'a [Stealer<Arc<Task>>],
+    // ワーカーを終了してよいかどうかを返す
+    done: &'a (dyn Fn() -> bool + Sync),
+    rand: FastRand,
+    tick: u32,
+}
+
+impl<'a> WorkerLoop<'a> {
+    pub(crate) fn new(
+        handle: &'a Handle,
+        index: usize,
+        queue: Worker<Arc<Task>>,
+        stealers: &'a [Stealer<Arc<Task>>],
+        done: &'a (dyn Fn() -> bool + Sync),
+    ) -> WorkerLoop<'a> {
+        WorkerLoop {
+            handle,
+            index,
+            queue: Rc::new(queue),
+            stealers,
+            done,
+            rand: FastRand::from_entropy((index, Instant::now())),
+            tick: 0,
+        }
+    }
+
+    pub(crate) fn run(mut self) {
+        let _enter = self.handle.enter();
+        CURRENT_QUEUE.with(|current| {
+            *current.borrow_mut() = Some(LocalQueue {
+                handle: self.handle.clone(),
+                queue: self.queue.clone(),
+            })
+        });
+        self.handle.inc_busy();
+
+        while !(self.done)() {
+            self.tick = self.tick.wrapping_add(1);
+
+            // タスクが途切れない場合でもタイマーや I/O イベントの処理が遅れすぎないよう、定期的に処理する
+            if self.tick.is_multiple_of(GLOBAL_QUEUE_INTERVAL) {
+                self.handle.process_timers();
+                self.handle.io().poll_now();
+            }
+
+            if let Some(task) = self.next_task() {
+                task.poll();
+                continue;
+            }
+
+            // 実行できるタスクがないので、期限を過ぎたタイマーを呼び起こしてから眠る
+            // [MEMO]
+            // タイマーが残っている場合は、次の期限までしか眠らない。
+            // 期限が来たらループの先頭に戻り、タイマーの "waker" を呼び起こす。
+            // [MEMO]
+            // 最初に眠るワーカーは epoll で I/O イベントを待つ。他のワーカーは条件変数で眠る。
+            // [MEMO]
+            // 期限を確認した後に、より早い期限のタイマーが登録されることがある。
+            // 登録した側は眠っているワーカーを起こすが、まだ眠りに入っていなければ起こし損ねるので、
+            // 眠る直前にもタイマーを確認する。
+            let next_deadline = self.handle.process_timers();
+
+            // 時刻が一時停止されていて、全ての参加者が眠ったら、眠らずに次のタイマーの期限まで時刻を進める
+            // [MEMO]
+            // ワーカー自身が最後に眠る参加者になることもあるので、数を減らしてから確認する。
+            self.handle.shared.busy.fetch_sub(1, Ordering::SeqCst);
+            if self.handle.auto_advance(|| self.has_work()) {
+                self.handle.inc_busy();
+                continue;
+            }
+
+            let has_work = || {
+                self.has_work()
+                    || (self.done)()
+                    || self.handle.has_earlier_timer(next_deadline)
+                    || self.handle.can_auto_advance()
+            };
+            let park_deadline = self.handle.clock().park_deadline(next_deadline);
+
+            if !self.handle.io().try_park(park_deadline, has_work) {
+                self.handle.shared.idle.park(park_deadline, has_work);
+            }
+
+            self.handle.inc_busy();
+        }
+
+        self.handle.shared.busy.fetch_sub(1, Ordering::SeqCst);
+
+        // [MEMO]
+        // `block_on` の終了によってワーカーが止まる場合は、ローカルキューにまだ実行されていないタスクが残っている。
+        // 次に `run` や `block_on` が呼ばれたときに実行されるよう、グローバルキューに移しておく。
+        // 停止したランタイムのタスクは、ローカルキューと一緒に破棄する。
+        CURRENT_QUEUE.with(|current| *current.borrow_mut() = None);
+
+        if !self.handle.is_shutdown() {
+            while let Some(task) = self.queue.pop() {
+                self.handle.shared.injector.push(task);
+            }
+        }
+    }
+
+    fn next_task(&mut self) -> Option<Arc<Task>> {
+        let injector = &self.handle.shared.injector;
+
+        if self.tick.is_multiple_of(GLOBAL_QUEUE_INTERVAL) {
+            if let Some(task) = steal_from(|| injector.steal_batch_and_pop(&self.queue)) {
+                return Some(task);
+            }
+        }
+
+        if let Some(task) = self.queue.pop() {
+            return Some(task);
+        }
+
+        if let Some(task) = steal_from(|| injector.steal_batch_and_pop(&self.queue)) {
+            return Some(task);
+        }
+
+        self.steal_from_others()
+    }
+
+    /// 他のワーカーのローカルキューからタスクを盗む
+    ///
+    /// 特定のワーカーに盗みが集中しないよう、ランダムに選んだワーカーから順に確認する。
+    fn steal_from_others(&mut self) -> Option<Arc<Task>> {
+        let n = self.stealers.len();
+        let start = self.rand.below(n);
+
+        for i in 0..n {
+            let index = (start + i) % n;
+            if index == self.index {
+                continue;
+            }
+
+            let stealer = &self.stealers[index];
+            if let Some(task) = steal_from(|| stealer.steal_batch_and_pop(&self.queue)) {
+                return Some(task);
+            }
+        }
+
+        None
+    }
+
+    fn has_work(&self) -> bool {
+        !self.queue.is_empty()
+            || !self.handle.shared.injector.is_empty()
+            || self.stealers.iter().any(|stealer| !stealer.is_empty())
+    }
+}
+
+/// `Steal::Retry` が返る間は盗むのを繰り返す
+fn steal_from(mut steal: impl FnMut() -> Steal<Arc<Task>>) -> Option<Arc<Task>> {
+    loop {
+        match steal() {
+            Steal::Success(task) => return Some(task),
+            Steal::Empty => return None,
+            Steal::Retry => continue,
+        }
+    }
+}
+
+/// 実行するタスクがないワーカーを眠らせ、タスクがスケジュールされたら起こす
+pub(crate) struct Idle {
+    lock: Mutex<()>,
+    condvar: Condvar,
+    // 眠っている（眠ろうとしている）ワーカーの数
+    sleepers: AtomicUsize,
+}
+
+impl Idle {
+    pub(crate) fn new() -> Idle {
+        Idle {
+            lock: Mutex::new(()),
+            condvar: Condvar::new(),
+            sleepers: AtomicUsize::new(0),
+        }
+    }
+
+    /// `deadline` まで、または `notify_one` が呼ばれるまで眠る
+    pub(super) fn park(&self, deadline: Option<Instant>, has_work: impl Fn() -> bool) {
+        let guard = self.lock.lock().unwrap();
+        self.sleepers.fetch_add(1, Ordering::SeqCst);
+
+        // [MEMO]
+        // `sleepers` を増やしてから改めてキューを確認する。
+        // `notify_one` 側はタスクをプッシュしてから `sleepers` を確認するので、
+        // どちらかが必ず相手の操作を観測でき、起こし忘れが起きない。
+        atomic::fence(Ordering::SeqCst);
+
+        if !has_work() {
+            match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if deadline > now {
+                        drop(self.condvar.wait_timeout(guard, deadline - now).unwrap());
+                    }
+                }
+                None => {
+                    drop(self.condvar.wait(guard).unwrap());
+                }
+            }
+        }
+
+        self.sleepers.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// 眠っているワーカーを全て起こす
+    pub(crate) fn notify_all(&self) {
+        drop(self.lock.lock().unwrap());
+        self.condvar.notify_all();
+    }
+
+    /// 眠っているワーカーがいれば1つ起こす。起こした場合は `true` を返す
+    pub(crate) fn notify_one(&self) -> bool {
+        atomic::fence(Ordering::SeqCst);
+
+        if self.sleepers.load(Ordering::SeqCst) > 0 {
+            // 眠ろうとしているワーカーが `wait` に入るまで待ってから通知する
+            drop(self.lock.lock().unwrap());
+            self.condvar.notify_one();
+            true
+        } else {
+            false
+        }
+    }
+}
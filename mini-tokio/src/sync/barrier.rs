@@ -0,0 +1,128 @@
+use crate::sync::Notify;
+use std::fmt;
+use std::sync::Mutex;
+
+/// 決められた数のタスクが揃うまで待ち合わせる
+///
+/// `wait` を呼んだタスクが `n` 個に達すると、待っていたタスクが全て起こされる。
+/// 揃った後のバリアは再び使うことができる。
+///
+/// ```no_run
+/// use mini_tokio::sync::Barrier;
+/// use mini_tokio::MiniTokio;
+/// use std::sync::Arc;
+///
+/// let mini_tokio = MiniTokio::new();
+///
+/// mini_tokio.block_on(async {
+///     let barrier = Arc::new(Barrier::new(10));
+///
+///     let handles: Vec<_> = (0..10)
+///         .map(|_| {
+///             let barrier = barrier.clone();
+///             mini_tokio::spawn(async move {
+///                 println!("before wait");
+///                 let result = barrier.wait().await;
+///                 println!("after wait");
+///                 result.is_leader()
+///             })
+///         })
+///         .collect();
+///
+///     let mut leaders = 0;
+///     for handle in handles {
+///         if handle.await.unwrap() {
+///             leaders += 1;
+///         }
+///     }
+///
+///     // 最後に到着した1つのタスクだけがリーダーになる
+///     assert_eq!(leaders, 1);
+/// });
+/// ```
+pub struct Barrier {
+    state: Mutex<State>,
+    // 全てのタスクが揃ったことを知らせる
+    notify: Notify,
+    n: usize,
+}
+
+struct State {
+    // 現在の世代で到着したタスクの数
+    arrived: usize,
+    // 全てのタスクが揃うたびに増える
+    generation: u64,
+}
+
+/// `Barrier::wait` の結果
+#[derive(Debug, Clone)]
+pub struct BarrierWaitResult(bool);
+
+impl Barrier {
+    /// `n` 個のタスクを待ち合わせるバリアを作る
+    ///
+    /// `n` が 0 の場合は 1 として扱う。
+    pub fn new(n: usize) -> Barrier {
+        Barrier {
+            state: Mutex::new(State {
+                arrived: 0,
+                generation: 0,
+            }),
+            notify: Notify::new(),
+            n: n.max(1),
+        }
+    }
+
+    /// 全てのタスクが `wait` を呼ぶまで待つ
+    ///
+    /// 最後に到着したタスクだけが、`is_leader` が `true` の結果を受け取る。
+    ///
+    /// [MEMO]
+    /// 待っている途中で "future" が破棄されても、到着した数は減らない。
+    /// そのため、キャンセルされると他のタスクが想定より早く起こされることがある。
+    pub async fn wait(&self) -> BarrierWaitResult {
+        let (generation, notified) = {
+            let mut state = self.state.lock().unwrap();
+            state.arrived += 1;
+
+            if state.arrived == self.n {
+                // 最後に到着したタスクが、待っているタスクを全て起こす
+                state.arrived = 0;
+                state.generation += 1;
+                drop(state);
+
+                self.notify.notify_waiters();
+                return BarrierWaitResult(true);
+            }
+
+            // [MEMO]
+            // `notified()` はロックを取ったまま作る。
+            // 作った時点より後の `notify_waiters` は、まだ `.await` していなくても受け取ることができる。
+            (state.generation, self.notify.notified())
+        };
+
+        let mut notified = Box::pin(notified);
+        loop {
+            notified.as_mut().await;
+
+            if self.state.lock().unwrap().generation != generation {
+                return BarrierWaitResult(false);
+            }
+
+            notified.set(self.notify.notified());
+        }
+    }
+}
+
+impl fmt::Debug for Barrier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Barrier").field("n", &self.n).finish()
+    }
+}
+
+impl BarrierWaitResult {
+    /// 最後に到着したタスクであれば `true` を返す
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
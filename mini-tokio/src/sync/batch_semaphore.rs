@@ -0,0 +1,293 @@
+use crate::sync::linked_list::{LinkedList, Node};
+use std::cell::UnsafeCell;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::sync::{Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+/// 一度に複数の許可（permit）を獲得できる、公平なセマフォ
+///
+/// `Mutex` や `RwLock`、公開している `Semaphore` の土台として使う。
+/// 許可を待っているタスクは到着順に並び、先頭のタスクが必要な数の許可が揃うまで、後ろのタスクは追い越さない。
+/// [MEMO]
+/// `RwLock` では、書き込みは全ての許可を、読み込みは1つの許可を獲得する。
+/// 書き込みを待っているタスクがいる間は後から来た読み込みも待たされるので、書き込みが飢餓状態にならない。
+pub(crate) struct Semaphore {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    // すぐに獲得できる許可の数
+    permits: usize,
+    // 許可を待っているタスク
+    waiters: LinkedList<Waiter>,
+    closed: bool,
+}
+
+/// 許可を待っているタスク
+struct Waiter {
+    // 獲得したい許可の数
+    needed: usize,
+    waker: Option<Waker>,
+    // 許可が割り当てられたかどうか
+    assigned: bool,
+}
+
+/// セマフォが閉じられていて、許可を獲得できなかったことを表すエラー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcquireError(());
+
+impl fmt::Display for AcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "semaphore closed")
+    }
+}
+
+impl Error for AcquireError {}
+
+/// 待たずに許可を獲得できなかったことを表すエラー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryAcquireError {
+    /// セマフォが閉じられている
+    Closed,
+    /// 許可が足りない
+    NoPermits,
+}
+
+impl fmt::Display for TryAcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryAcquireError::Closed => write!(f, "semaphore closed"),
+            TryAcquireError::NoPermits => write!(f, "no permits available"),
+        }
+    }
+}
+
+impl Error for TryAcquireError {}
+
+impl Semaphore {
+    pub(crate) const fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            inner: Mutex::new(Inner {
+                permits,
+                waiters: LinkedList::new(),
+                closed: false,
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap()
+    }
+
+    /// `num_permits` 個の許可を獲得する
+    pub(crate) fn acquire(&self, num_permits: usize) -> Acquire<'_> {
+        Acquire {
+            semaphore: self,
+            node: UnsafeCell::new(Node::new(Waiter {
+                needed: num_permits,
+                waker: None,
+                assigned: false,
+            })),
+            queued: false,
+        }
+    }
+
+    /// 待たずに `num_permits` 個の許可の獲得を試みる
+    ///
+    /// 許可を待っているタスクがいる場合は、許可が残っていても追い越さずに失敗する。
+    pub(crate) fn try_acquire(&self, num_permits: usize) -> Result<(), TryAcquireError> {
+        let mut inner = self.lock();
+
+        if inner.closed {
+            return Err(TryAcquireError::Closed);
+        }
+
+        if inner.waiters.front().is_none() && inner.permits >= num_permits {
+            inner.permits -= num_permits;
+            Ok(())
+        } else {
+            Err(TryAcquireError::NoPermits)
+        }
+    }
+
+    /// `num_permits` 個の許可を返す
+    ///
+    /// 先頭で待っているタスクから順に、必要な数の許可が揃ったタスクだけを起こす。
+    pub(crate) fn release(&self, num_permits: usize) {
+        let wakers = {
+            let mut inner = self.lock();
+            inner.permits += num_permits;
+            inner.assign_permits()
+        };
+
+        // ロックを解放してから起こす
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// セマフォを閉じ、待っているタスクを全て起こして `AcquireError` を返させる
+    pub(crate) fn close(&self) {
+        let wakers: Vec<_> = {
+            let mut inner = self.lock();
+            inner.closed = true;
+
+            std::iter::from_fn(|| inner.waiters.pop_front())
+                .filter_map(|node| {
+                    // SAFETY: リストに入っているノードは有効で、ロックの中でアクセスしている
+                    unsafe { (*node.as_ptr()).value.waker.take() }
+                })
+                .collect()
+        };
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.lock().closed
+    }
+
+    /// すぐに獲得できる許可の数を返す
+    pub(crate) fn available_permits(&self) -> usize {
+        self.lock().permits
+    }
+}
+
+impl Inner {
+    /// 先頭で待っているタスクから順に許可を割り当て、起こすタスクの "waker" を返す
+    ///
+    /// 先頭のタスクに必要な数の許可が揃わなければ、そこで止める。
+    fn assign_permits(&mut self) -> Vec<Waker> {
+        let mut wakers = Vec::new();
+
+        while let Some(node) = self.waiters.front() {
+            // SAFETY: リストに入っているノードは有効で、ロックの中でアクセスしている
+            let waiter = unsafe { &mut (*node.as_ptr()).value };
+
+            if self.permits < waiter.needed {
+                break;
+            }
+
+            self.permits -= waiter.needed;
+            waiter.assigned = true;
+            wakers.extend(waiter.waker.take());
+            self.waiters.pop_front();
+        }
+
+        wakers
+    }
+}
+
+/// 許可を獲得する "future"
+///
+/// 許可が足りない場合は、自身のノードを待機リストに入れて `Pending` を返す。
+pub(crate) struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    // 待機リストに入れるノード
+    // [MEMO]
+    // ノードは "future" と一緒にピン留めされるため、リストに入っている間に移動することはない。
+    // リストから参照されている間は、ロックの中で生ポインタを通してのみアクセスする。
+    node: UnsafeCell<Node<Waiter>>,
+    // 一度でも待機リストに入ったかどうか
+    queued: bool,
+}
+
+// SAFETY: `node` にはセマフォのロックの中でしかアクセスしない
+unsafe impl Send for Acquire<'_> {}
+unsafe impl Sync for Acquire<'_> {}
+
+impl Future for Acquire<'_> {
+    type Output = Result<(), AcquireError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), AcquireError>> {
+        // SAFETY: `self` を移動させることはない
+        let this = unsafe { self.get_unchecked_mut() };
+        let node = NonNull::new(this.node.get()).unwrap();
+
+        let mut inner = this.semaphore.lock();
+
+        // SAFETY: ノードにはロックの中でアクセスしている
+        let (linked, waiter) = unsafe {
+            let node = &mut *node.as_ptr();
+            (node.is_linked(), &mut node.value)
+        };
+
+        if waiter.assigned {
+            // [MEMO]
+            // 割り当てられた許可は呼び出し側のものになるので、`Drop` で返さないように印を消しておく
+            waiter.assigned = false;
+            return Poll::Ready(Ok(()));
+        }
+
+        if inner.closed {
+            // SAFETY: ノードはこのセマフォの待機リストにしか入らない
+            unsafe { inner.waiters.remove(node) };
+            return Poll::Ready(Err(AcquireError(())));
+        }
+
+        if linked {
+            match &mut waiter.waker {
+                Some(waker) if waker.will_wake(cx.waker()) => {}
+                slot => *slot = Some(cx.waker().clone()),
+            }
+            return Poll::Pending;
+        }
+
+        // 待っているタスクがいなければ、その場で獲得する
+        if inner.waiters.front().is_none() && inner.permits >= waiter.needed {
+            inner.permits -= waiter.needed;
+            return Poll::Ready(Ok(()));
+        }
+
+        waiter.waker = Some(cx.waker().clone());
+        // SAFETY: ノードはピン留めされており、リストから取り除かれるまで `Drop` で破棄されない
+        unsafe { inner.waiters.push_back(node) };
+        this.queued = true;
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        // 一度も待機リストに入っていなければ、セマフォから参照されていることはない
+        if !self.queued {
+            return;
+        }
+
+        let node = NonNull::new(self.node.get()).unwrap();
+
+        let wakers = {
+            let mut inner = self.semaphore.lock();
+
+            // SAFETY: ノードはこのセマフォの待機リストにしか入らず、ロックの中でアクセスしている
+            unsafe {
+                let was_head = inner.waiters.front() == Some(node);
+                inner.waiters.remove(node);
+
+                let waiter = &mut (*node.as_ptr()).value;
+                if waiter.assigned {
+                    // 割り当てられたのに使われなかった許可は、次のタスクに回す
+                    inner.permits += waiter.needed;
+                    inner.assign_permits()
+                } else if was_head {
+                    // [MEMO]
+                    // 先頭のタスクがいなくなると、後ろのタスクが必要とする許可はすでに揃っているかもしれない
+                    inner.assign_permits()
+                } else {
+                    Vec::new()
+                }
+            }
+        };
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
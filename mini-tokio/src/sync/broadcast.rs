@@ -0,0 +1,423 @@
+//! 複数の送信側と複数の受信側をもつ、送信された全ての値を全ての受信側に届けるチャネル
+//!
+//! 値は容量が固定のリングバッファに書き込まれ、各受信側は自分の読み出し位置から順に値を複製して受け取る。
+//! 受信が追いつかず、読む前の値が上書きされた受信側は `RecvError::Lagged` で取りこぼした数を知る。
+//!
+//! ```no_run
+//! use mini_tokio::sync::broadcast;
+//! use mini_tokio::MiniTokio;
+//!
+//! let mini_tokio = MiniTokio::new();
+//!
+//! mini_tokio.block_on(async {
+//!     let (tx, mut rx1) = broadcast::channel(16);
+//!     let mut rx2 = tx.subscribe();
+//!
+//!     let handle = mini_tokio::spawn(async move {
+//!         assert_eq!(rx2.recv().await.unwrap(), 10);
+//!         assert_eq!(rx2.recv().await.unwrap(), 20);
+//!     });
+//!
+//!     tx.send(10).unwrap();
+//!     tx.send(20).unwrap();
+//!
+//!     assert_eq!(rx1.recv().await.unwrap(), 10);
+//!     assert_eq!(rx1.recv().await.unwrap(), 20);
+//!     handle.await.unwrap();
+//! });
+//! ```
+
+use crate::sync::linked_list::{LinkedList, Node};
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+/// 最大 `capacity` 個の値を保持するチャネルを作る
+///
+/// # Panics
+///
+/// `capacity` が 0 の場合はパニックする。
+pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "broadcast channel capacity cannot be zero");
+
+    let shared = Arc::new(Shared {
+        inner: Mutex::new(Inner {
+            buffer: (0..capacity).map(|_| None).collect(),
+            tail: 0,
+            tx_count: 1,
+            rx_count: 1,
+            waiters: LinkedList::new(),
+        }),
+    });
+
+    let rx = Receiver {
+        shared: shared.clone(),
+        next: 0,
+    };
+
+    (Sender { shared }, rx)
+}
+
+/// 全ての受信側に値を送信する。`clone` して複数のタスクから送信できる
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// 送信された値を受信する
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    // 次に受信する値の位置
+    next: u64,
+}
+
+struct Shared<T> {
+    inner: Mutex<Inner<T>>,
+}
+
+struct Inner<T> {
+    // 送信された値を保持するリングバッファ
+    // [MEMO]
+    // 位置 `pos` の値は `buffer[pos % capacity]` に書き込まれる。
+    // 容量を超えて送信されると、最も古い値から上書きされる。
+    buffer: Box<[Option<T>]>,
+    // 次に書き込む位置。これまでに送信された値の数と等しい
+    tail: u64,
+    // 破棄されていない `Sender` の数
+    tx_count: usize,
+    // 破棄されていない `Receiver` の数
+    rx_count: usize,
+    // 値が送信されるのを待っている受信側
+    waiters: LinkedList<Option<Waker>>,
+}
+
+pub mod error {
+    //! `broadcast` の操作が失敗したときのエラー
+
+    use std::error::Error;
+    use std::fmt;
+
+    /// 受信側が1つもなく、値を送信できなかったことを表すエラー
+    ///
+    /// 送信しようとした値を含んでいる。
+    pub struct SendError<T>(pub T);
+
+    impl<T> fmt::Debug for SendError<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("SendError").finish_non_exhaustive()
+        }
+    }
+
+    impl<T> fmt::Display for SendError<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "channel closed")
+        }
+    }
+
+    impl<T> Error for SendError<T> {}
+
+    /// `recv` が失敗したことを表すエラー
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum RecvError {
+        /// 全ての送信側が破棄され、これ以上受信できる値がない
+        Closed,
+        /// 受信が追いつかず、値を取りこぼした。取りこぼした値の数を含む
+        ///
+        /// 次の `recv` は、まだ残っている最も古い値から受信を再開する。
+        Lagged(u64),
+    }
+
+    impl fmt::Display for RecvError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                RecvError::Closed => write!(f, "channel closed"),
+                RecvError::Lagged(n) => write!(f, "channel lagged by {}", n),
+            }
+        }
+    }
+
+    impl Error for RecvError {}
+
+    /// `try_recv` が失敗したことを表すエラー
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum TryRecvError {
+        /// 受信できる値がまだない
+        Empty,
+        /// 全ての送信側が破棄され、これ以上受信できる値がない
+        Closed,
+        /// 受信が追いつかず、値を取りこぼした。取りこぼした値の数を含む
+        Lagged(u64),
+    }
+
+    impl fmt::Display for TryRecvError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                TryRecvError::Empty => write!(f, "channel empty"),
+                TryRecvError::Closed => write!(f, "channel closed"),
+                TryRecvError::Lagged(n) => write!(f, "channel lagged by {}", n),
+            }
+        }
+    }
+
+    impl Error for TryRecvError {}
+}
+
+use error::{RecvError, SendError, TryRecvError};
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, Inner<T>> {
+        self.inner.lock().unwrap()
+    }
+}
+
+impl<T> Inner<T> {
+    /// 待っている受信側を全て取り除き、その "waker" を返す
+    ///
+    /// "waker" の呼び出しはロックを解放してから行うこと。
+    fn take_waiters(&mut self) -> Vec<Waker> {
+        std::iter::from_fn(|| self.waiters.pop_front())
+            .filter_map(|node| {
+                // SAFETY: リストに入っているノードは有効で、ロックの中でアクセスしている
+                unsafe { (*node.as_ptr()).value.take() }
+            })
+            .collect()
+    }
+}
+
+impl<T: Clone> Inner<T> {
+    /// 位置 `next` の値の受信を試みる
+    ///
+    /// 受信できた場合や取りこぼしがあった場合は、`next` を次に受信する位置に進める。
+    /// まだ値が送信されていない場合は `Ok(None)` を返す。
+    fn try_recv_at(&self, next: &mut u64) -> Result<Option<T>, RecvError> {
+        if *next == self.tail {
+            return if self.tx_count == 0 {
+                Err(RecvError::Closed)
+            } else {
+                Ok(None)
+            };
+        }
+
+        // 上書きされずに残っている最も古い値の位置
+        let capacity = self.buffer.len() as u64;
+        let oldest = self.tail.saturating_sub(capacity);
+
+        if *next < oldest {
+            let missed = oldest - *next;
+            *next = oldest;
+            return Err(RecvError::Lagged(missed));
+        }
+
+        let value = self.buffer[(*next % capacity) as usize].clone();
+        *next += 1;
+        Ok(value)
+    }
+}
+
+impl<T> Sender<T> {
+    /// 全ての受信側に値を送信し、送信時点の受信側の数を返す
+    ///
+    /// 受信側が1つもない場合は、送信しようとした値を含む `SendError` を返す。
+    /// 送信は待たずに完了する。受信が遅い受信側は、古い値を取りこぼすことがある。
+    pub fn send(&self, value: T) -> Result<usize, SendError<T>> {
+        let (receivers, wakers, old) = {
+            let mut inner = self.shared.lock();
+
+            if inner.rx_count == 0 {
+                return Err(SendError(value));
+            }
+
+            let idx = (inner.tail % inner.buffer.len() as u64) as usize;
+            let old = inner.buffer[idx].replace(value);
+            inner.tail += 1;
+
+            (inner.rx_count, inner.take_waiters(), old)
+        };
+
+        // 上書きした古い値はロックを解放してから破棄する
+        drop(old);
+
+        for waker in wakers {
+            waker.wake();
+        }
+
+        Ok(receivers)
+    }
+
+    /// 新しい受信側を作る
+    ///
+    /// 作られた受信側は、これ以降に送信された値を受信する。
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut inner = self.shared.lock();
+        inner.rx_count += 1;
+
+        Receiver {
+            shared: self.shared.clone(),
+            next: inner.tail,
+        }
+    }
+
+    /// 受信側の数を返す
+    pub fn receiver_count(&self) -> usize {
+        self.shared.lock().rx_count
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        self.shared.lock().tx_count += 1;
+
+        Sender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let wakers = {
+            let mut inner = self.shared.lock();
+            inner.tx_count -= 1;
+
+            // 最後の `Sender` が破棄されたら、待っている受信側を起こして `Closed` を返させる
+            if inner.tx_count == 0 {
+                inner.take_waiters()
+            } else {
+                Vec::new()
+            }
+        };
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+impl<T: Clone> Receiver<T> {
+    /// 次の値を受信する
+    ///
+    /// 値がまだ送信されていない場合は、送信されるまで待つ。
+    /// 取りこぼした値があれば `RecvError::Lagged` を、全ての送信側が破棄されて
+    /// 受信できる値がなくなれば `RecvError::Closed` を返す。
+    pub async fn recv(&mut self) -> Result<T, RecvError> {
+        Recv::new(self).await
+    }
+
+    /// 待たずに次の値を受信する
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let inner = self.shared.lock();
+
+        match inner.try_recv_at(&mut self.next) {
+            Ok(Some(value)) => Ok(value),
+            Ok(None) => Err(TryRecvError::Empty),
+            Err(RecvError::Closed) => Err(TryRecvError::Closed),
+            Err(RecvError::Lagged(n)) => Err(TryRecvError::Lagged(n)),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.lock().rx_count -= 1;
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+/// 次の値を受信する "future"
+///
+/// 受信できる値がない場合は、自身のノードを待機リストに入れて `Pending` を返す。
+/// 値が送信されると待機リストの受信側は全て起こされ、再度ポーリングされたときに値を受信する。
+struct Recv<'a, T> {
+    receiver: &'a mut Receiver<T>,
+    // 待機リストに入れるノード。扱いは `mpsc` の `Acquire` と同じ
+    node: UnsafeCell<Node<Option<Waker>>>,
+    // 一度でも待機リストに入ったかどうか
+    queued: bool,
+}
+
+// SAFETY: `node` にはチャネルのロックの中でしかアクセスしない
+unsafe impl<T: Send> Send for Recv<'_, T> {}
+
+impl<'a, T> Recv<'a, T> {
+    fn new(receiver: &'a mut Receiver<T>) -> Recv<'a, T> {
+        Recv {
+            receiver,
+            node: UnsafeCell::new(Node::new(None)),
+            queued: false,
+        }
+    }
+}
+
+impl<T: Clone> Future for Recv<'_, T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        // SAFETY: `self` を移動させることはない
+        let this = unsafe { self.get_unchecked_mut() };
+        let node = NonNull::new(this.node.get()).unwrap();
+
+        let mut inner = this.receiver.shared.lock();
+
+        match inner.try_recv_at(&mut this.receiver.next) {
+            Ok(Some(value)) => {
+                // SAFETY: ノードはこのチャネルの待機リストにしか入らない
+                unsafe { inner.waiters.remove(node) };
+                Poll::Ready(Ok(value))
+            }
+            Err(e) => {
+                // SAFETY: 同上
+                unsafe { inner.waiters.remove(node) };
+                Poll::Ready(Err(e))
+            }
+            Ok(None) => {
+                // SAFETY: ノードにはロックの中でアクセスしている
+                let (linked, waker) = unsafe {
+                    let node = &mut *node.as_ptr();
+                    (node.is_linked(), &mut node.value)
+                };
+
+                match waker {
+                    Some(waker) if waker.will_wake(cx.waker()) => {}
+                    waker => *waker = Some(cx.waker().clone()),
+                }
+
+                if !linked {
+                    // SAFETY: ノードはピン留めされており、リストから取り除かれるまで `Drop` で破棄されない
+                    unsafe { inner.waiters.push_back(node) };
+                    this.queued = true;
+                }
+
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for Recv<'_, T> {
+    fn drop(&mut self) {
+        // 一度も待機リストに入っていなければ、チャネルから参照されていることはない
+        if !self.queued {
+            return;
+        }
+
+        let node = NonNull::new(self.node.get()).unwrap();
+        let mut inner = self.receiver.shared.lock();
+
+        // SAFETY: ノードはこのチャネルの待機リストにしか入らず、ロックの中でアクセスしている
+        unsafe { inner.waiters.remove(node) };
+    }
+}
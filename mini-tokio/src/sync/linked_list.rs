@@ -0,0 +1,117 @@
+use std::marker::PhantomPinned;
+use std::ptr::NonNull;
+
+/// 侵入型（intrusive）の双方向連結リスト
+///
+/// ノードのメモリはリストではなく、待機している "future" 自身が持つ。
+/// 待機者ごとにヒープ確保をせずに済み、"future" が破棄されたときにはリストから自分を取り除ける。
+///
+/// [MEMO]
+/// リストはノードを生ポインタで参照するため、次のことは呼び出し側が保証する必要がある。
+/// - ノードはリストに入っている間、移動も破棄もされない（"future" をピン留めし、`Drop` で取り除く）
+/// - リストとノードには、同じロックを取得した状態でしかアクセスしない
+pub(crate) struct LinkedList<T> {
+    head: Option<NonNull<Node<T>>>,
+    tail: Option<NonNull<Node<T>>>,
+}
+
+/// リストのノード。待機者の "future" の中に置かれる
+pub(crate) struct Node<T> {
+    prev: Option<NonNull<Node<T>>>,
+    next: Option<NonNull<Node<T>>>,
+    // リストに入っているかどうか
+    linked: bool,
+    pub(crate) value: T,
+    // リストから参照されている間に移動されないよう、`Unpin` を実装しない
+    _pin: PhantomPinned,
+}
+
+// SAFETY: ノードへのアクセスはリストを保護するロックの中で行われる
+unsafe impl<T: Send> Send for LinkedList<T> {}
+unsafe impl<T: Send> Sync for LinkedList<T> {}
+
+impl<T> LinkedList<T> {
+    pub(crate) const fn new() -> LinkedList<T> {
+        LinkedList {
+            head: None,
+            tail: None,
+        }
+    }
+
+    /// ノードを末尾に追加する
+    ///
+    /// # Safety
+    ///
+    /// `node` はリストに入っていない有効なノードで、リストから取り除かれるまで移動も破棄もされないこと。
+    pub(crate) unsafe fn push_back(&mut self, node: NonNull<Node<T>>) {
+        let ptr = node.as_ptr();
+        debug_assert!(!(*ptr).linked);
+
+        (*ptr).prev = self.tail;
+        (*ptr).next = None;
+        (*ptr).linked = true;
+
+        match self.tail {
+            Some(tail) => (*tail.as_ptr()).next = Some(node),
+            None => self.head = Some(node),
+        }
+        self.tail = Some(node);
+    }
+
+    /// 先頭のノードを取り除いて返す
+    pub(crate) fn pop_front(&mut self) -> Option<NonNull<Node<T>>> {
+        let node = self.head?;
+
+        // SAFETY: リストに入っているノードは `push_back` の約束により有効である
+        unsafe { self.remove(node) };
+        Some(node)
+    }
+
+    /// 先頭のノードを返す。リストからは取り除かない
+    pub(crate) fn front(&self) -> Option<NonNull<Node<T>>> {
+        self.head
+    }
+
+    /// ノードをリストから取り除く。リストに入っていなかった場合は `false` を返す
+    ///
+    /// # Safety
+    ///
+    /// `node` は有効なノードで、このリスト以外のリストに入っていないこと。
+    pub(crate) unsafe fn remove(&mut self, node: NonNull<Node<T>>) -> bool {
+        let ptr = node.as_ptr();
+        if !(*ptr).linked {
+            return false;
+        }
+
+        match (*ptr).prev {
+            Some(prev) => (*prev.as_ptr()).next = (*ptr).next,
+            None => self.head = (*ptr).next,
+        }
+        match (*ptr).next {
+            Some(next) => (*next.as_ptr()).prev = (*ptr).prev,
+            None => self.tail = (*ptr).prev,
+        }
+
+        (*ptr).prev = None;
+        (*ptr).next = None;
+        (*ptr).linked = false;
+        true
+    }
+}
+
+impl<T> Node<T> {
+    pub(crate) const fn new(value: T) -> Node<T> {
+        Node {
+            prev: None,
+            next: None,
+            linked: false,
+            value,
+            _pin: PhantomPinned,
+        }
+    }
+
+    /// リストに入っているかどうか
+    pub(crate) fn is_linked(&self) -> bool {
+        self.linked
+    }
+}
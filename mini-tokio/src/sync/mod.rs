@@ -0,0 +1,28 @@
+//! タスク間で値を受け渡したり、待ち合わせたりするための同期プリミティブ
+//!
+//! どのプリミティブも、待っているタスクをスレッドごと止めるのではなく、
+//! "waker" を登録して `Pending` を返し、条件が整ったときに呼び起こす。
+
+mod batch_semaphore;
+pub use batch_semaphore::{AcquireError, TryAcquireError};
+mod linked_list;
+
+mod barrier;
+pub use barrier::{Barrier, BarrierWaitResult};
+
+mod mutex;
+pub use mutex::{Mutex, MutexGuard, TryLockError};
+
+mod notify;
+pub use notify::{Notified, Notify, OwnedNotified};
+
+mod rwlock;
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+mod semaphore;
+pub use semaphore::{OwnedSemaphorePermit, Semaphore, SemaphorePermit};
+
+pub mod broadcast;
+pub mod mpsc;
+pub mod oneshot;
+pub mod watch;
@@ -0,0 +1,416 @@
+use crate::sync::linked_list::{LinkedList, Node};
+use crate::sync::mpsc::error::{SendError, TryRecvError, TrySendError};
+use std::cell::UnsafeCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+/// 最大 `buffer` 個の値を保持できるチャネルを作る
+///
+/// # Panics
+///
+/// `buffer` が 0 の場合はパニックする。
+pub fn channel<T>(buffer: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(buffer > 0, "mpsc bounded channel requires buffer > 0");
+
+    let chan = Arc::new(Chan {
+        inner: Mutex::new(Inner {
+            buffer: VecDeque::with_capacity(buffer),
+            permits: buffer,
+            send_waiters: LinkedList::new(),
+            rx_waker: None,
+            tx_count: 1,
+            closed: false,
+        }),
+    });
+
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+/// チャネルに値を送信する。`clone` して複数のタスクから送信できる
+pub struct Sender<T> {
+    chan: Arc<Chan<T>>,
+}
+
+/// チャネルから値を受信する
+pub struct Receiver<T> {
+    chan: Arc<Chan<T>>,
+}
+
+/// 送信側と受信側で共有されるチャネルの本体
+struct Chan<T> {
+    inner: Mutex<Inner<T>>,
+}
+
+struct Inner<T> {
+    // 受信されるのを待っている値
+    buffer: VecDeque<T>,
+    // すぐに使える空き枠の数
+    // [MEMO]
+    // 空き枠は「値を1つ送信する権利（permit）」として扱う。
+    // 受信によって空いた枠は、待っている送信側がいればその送信側に直接割り当てるので、ここには戻らない。
+    // 後から来た送信側に枠を横取りされず、待っている送信側は到着順に送信できる。
+    permits: usize,
+    // 空き枠を待っている送信側
+    send_waiters: LinkedList<Waiter>,
+    // 値を待っている受信側の "waker"
+    rx_waker: Option<Waker>,
+    // 破棄されていない `Sender` の数
+    tx_count: usize,
+    // 受信側が閉じられたかどうか
+    closed: bool,
+}
+
+/// 空き枠を待っている送信側
+struct Waiter {
+    waker: Option<Waker>,
+    // 空き枠が割り当てられたかどうか
+    assigned: bool,
+}
+
+impl<T> Chan<T> {
+    fn lock(&self) -> MutexGuard<'_, Inner<T>> {
+        self.inner.lock().unwrap()
+    }
+}
+
+impl<T> Inner<T> {
+    /// 空き枠を1つ返す
+    ///
+    /// 待っている送信側がいればその送信側に割り当て、呼び起こすための "waker" を返す。
+    /// "waker" の呼び出しはロックを解放してから行うこと。
+    fn release_permit(&mut self) -> Option<Waker> {
+        match self.send_waiters.pop_front() {
+            Some(node) => {
+                // SAFETY: リストに入っているノードは有効で、ロックの中でアクセスしている
+                let waiter = unsafe { &mut (*node.as_ptr()).value };
+                waiter.assigned = true;
+                waiter.waker.take()
+            }
+            None => {
+                self.permits += 1;
+                None
+            }
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// 値を送信する
+    ///
+    /// チャネルに空きがない場合は、受信側が値を受け取って空きができるまで待つ。
+    /// 受信側が閉じられている場合は、送信しようとした値を含む `SendError` を返す。
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        if Acquire::new(&self.chan).await.is_err() {
+            return Err(SendError(value));
+        }
+
+        let waker = {
+            let mut inner = self.chan.lock();
+
+            if inner.closed {
+                return Err(SendError(value));
+            }
+
+            inner.buffer.push_back(value);
+            inner.rx_waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+
+    /// 空きを待たずに値を送信する
+    ///
+    /// チャネルに空きがない場合は `TrySendError::Full` を、受信側が閉じられている場合は
+    /// `TrySendError::Closed` を返す。
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let waker = {
+            let mut inner = self.chan.lock();
+
+            if inner.closed {
+                return Err(TrySendError::Closed(value));
+            }
+
+            if inner.permits == 0 {
+                return Err(TrySendError::Full(value));
+            }
+
+            inner.permits -= 1;
+            inner.buffer.push_back(value);
+            inner.rx_waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+
+    /// 受信側が閉じられているかどうか
+    pub fn is_closed(&self) -> bool {
+        self.chan.lock().closed
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        self.chan.lock().tx_count += 1;
+
+        Sender {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let waker = {
+            let mut inner = self.chan.lock();
+            inner.tx_count -= 1;
+
+            // 最後の `Sender` が破棄されたら、受信側を起こして `None` を返させる
+            if inner.tx_count == 0 {
+                inner.rx_waker.take()
+            } else {
+                None
+            }
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+impl<T> Receiver<T> {
+    /// 次の値を受信する
+    ///
+    /// チャネルが空の場合は、値が送信されるまで待つ。
+    /// 全ての `Sender` が破棄されるか `close` が呼ばれ、チャネルが空になったら `None` を返す。
+    pub async fn recv(&mut self) -> Option<T> {
+        future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// 次の値の受信を試み、値がなければ `cx` の "waker" を登録する
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let (value, waker) = {
+            let mut inner = self.chan.lock();
+
+            match inner.buffer.pop_front() {
+                Some(value) => {
+                    let waker = inner.release_permit();
+                    (value, waker)
+                }
+                None if inner.closed || inner.tx_count == 0 => return Poll::Ready(None),
+                None => {
+                    match &mut inner.rx_waker {
+                        Some(waker) if waker.will_wake(cx.waker()) => {}
+                        rx_waker => *rx_waker = Some(cx.waker().clone()),
+                    }
+                    return Poll::Pending;
+                }
+            }
+        };
+
+        // 空いた枠を割り当てた送信側を起こす
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        Poll::Ready(Some(value))
+    }
+
+    /// 待たずに次の値を受信する
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let (value, waker) = {
+            let mut inner = self.chan.lock();
+
+            match inner.buffer.pop_front() {
+                Some(value) => {
+                    let waker = inner.release_permit();
+                    (value, waker)
+                }
+                None if inner.closed || inner.tx_count == 0 => {
+                    return Err(TryRecvError::Disconnected)
+                }
+                None => return Err(TryRecvError::Empty),
+            }
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        Ok(value)
+    }
+
+    /// チャネルを閉じ、これ以降の送信を失敗させる
+    ///
+    /// すでにチャネルに入っている値は、引き続き `recv` で受信できる。
+    pub fn close(&mut self) {
+        let wakers: Vec<_> = {
+            let mut inner = self.chan.lock();
+            inner.closed = true;
+
+            // 空き枠を待っている送信側を全て起こし、`SendError` を返させる
+            std::iter::from_fn(|| inner.send_waiters.pop_front())
+                .filter_map(|node| {
+                    // SAFETY: リストに入っているノードは有効で、ロックの中でアクセスしている
+                    unsafe { (*node.as_ptr()).value.waker.take() }
+                })
+                .collect()
+        };
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.close();
+
+        // 受信されなかった値は、ロックを解放してから破棄する
+        let buffer = std::mem::take(&mut self.chan.lock().buffer);
+        drop(buffer);
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+/// チャネルの空き枠を1つ獲得する "future"
+///
+/// 空きがない場合は、自身のノードを待機リストに入れて `Pending` を返す。
+/// 受信側が閉じられた場合は `Err(())` で完了する。
+struct Acquire<'a, T> {
+    chan: &'a Chan<T>,
+    // 待機リストに入れるノード
+    // [MEMO]
+    // ノードは "future" と一緒にピン留めされるため、リストに入っている間に移動することはない。
+    // リストから参照されている間は、ロックの中で生ポインタを通してのみアクセスする。
+    node: UnsafeCell<Node<Waiter>>,
+    // 一度でも待機リストに入ったかどうか
+    queued: bool,
+}
+
+// SAFETY: `node` にはチャネルのロックの中でしかアクセスしない
+unsafe impl<T: Send> Send for Acquire<'_, T> {}
+
+impl<'a, T> Acquire<'a, T> {
+    fn new(chan: &'a Chan<T>) -> Acquire<'a, T> {
+        Acquire {
+            chan,
+            node: UnsafeCell::new(Node::new(Waiter {
+                waker: None,
+                assigned: false,
+            })),
+            queued: false,
+        }
+    }
+}
+
+impl<T> Future for Acquire<'_, T> {
+    type Output = Result<(), ()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ()>> {
+        // SAFETY: `self` を移動させることはない
+        let this = unsafe { self.get_unchecked_mut() };
+        let node = NonNull::new(this.node.get()).unwrap();
+
+        let mut inner = this.chan.lock();
+
+        // SAFETY: ノードにはロックの中でアクセスしている
+        let (linked, waiter) = unsafe {
+            let node = &mut *node.as_ptr();
+            (node.is_linked(), &mut node.value)
+        };
+
+        // 受信によって空いた枠が割り当てられた
+        if waiter.assigned {
+            // [MEMO]
+            // 割り当てられた枠はこのまま送信に使うので、`Drop` で返さないように印を消しておく
+            waiter.assigned = false;
+            return Poll::Ready(Ok(()));
+        }
+
+        if inner.closed {
+            // SAFETY: ノードはこのチャネルの待機リストにしか入らない
+            unsafe { inner.send_waiters.remove(node) };
+            return Poll::Ready(Err(()));
+        }
+
+        if linked {
+            // まだ枠が割り当てられていない。"waker" だけを更新する
+            match &mut waiter.waker {
+                Some(waker) if waker.will_wake(cx.waker()) => {}
+                slot => *slot = Some(cx.waker().clone()),
+            }
+            return Poll::Pending;
+        }
+
+        // [MEMO]
+        // `permits` が残っているのは待っている送信側がいないときだけなので、ここで取っても順番は崩れない
+        if inner.permits > 0 {
+            inner.permits -= 1;
+            return Poll::Ready(Ok(()));
+        }
+
+        waiter.waker = Some(cx.waker().clone());
+        // SAFETY: ノードはピン留めされており、リストから取り除かれるまで `Drop` で破棄されない
+        unsafe { inner.send_waiters.push_back(node) };
+        this.queued = true;
+
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Acquire<'_, T> {
+    fn drop(&mut self) {
+        // 一度も待機リストに入っていなければ、チャネルから参照されていることはない
+        if !self.queued {
+            return;
+        }
+
+        let node = NonNull::new(self.node.get()).unwrap();
+
+        let waker = {
+            let mut inner = self.chan.lock();
+
+            // SAFETY: ノードはこのチャネルの待機リストにしか入らず、ロックの中でアクセスしている
+            unsafe {
+                inner.send_waiters.remove(node);
+
+                // 枠が割り当てられたのに使わずに破棄される場合は、次の送信側に回す
+                if (*node.as_ptr()).value.assigned {
+                    inner.release_permit()
+                } else {
+                    None
+                }
+            }
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! 複数の送信側と1つの受信側をもつ、容量制限つきのチャネル
+//!
+//! チャネルがいっぱいのときは `send` が、空のときは `recv` が `Pending` を返し、
+//! 相手側の操作によって "waker" が呼ばれるまでタスクを待たせる（バックプレッシャー）。
+//!
+//! ```no_run
+//! use mini_tokio::sync::mpsc;
+//! use mini_tokio::MiniTokio;
+//!
+//! let mini_tokio = MiniTokio::new();
+//!
+//! mini_tokio.block_on(async {
+//!     let (tx, mut rx) = mpsc::channel(32);
+//!
+//!     for i in 0..10 {
+//!         let tx = tx.clone();
+//!         mini_tokio::spawn(async move {
+//!             tx.send(i).await.unwrap();
+//!         });
+//!     }
+//!     // 全ての `Sender` が破棄されると `recv` は `None` を返す
+//!     drop(tx);
+//!
+//!     while let Some(i) = rx.recv().await {
+//!         println!("got = {}", i);
+//!     }
+//! });
+//! ```
+
+mod bounded;
+pub use bounded::{channel, Receiver, Sender};
+
+pub mod error;
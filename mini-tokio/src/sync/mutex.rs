@@ -0,0 +1,164 @@
+use crate::sync::batch_semaphore::Semaphore;
+use std::cell::UnsafeCell;
+use std::error::Error;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+/// `.await` をまたいでロックを保持できる、非同期の排他ロック
+///
+/// ロックを待っているタスクはスレッドを止めずに `Pending` を返し、到着順にロックを獲得する。
+/// ロックが解放されると、待っているタスクのうち先頭の1つだけが起こされる。
+///
+/// [MEMO]
+/// `std::sync::Mutex` のガードを保持したまま `.await` すると、同じワーカーで実行される別のタスクが
+/// ロックを取ろうとしてスレッドごと止まり、デッドロックすることがある。
+/// `.await` をまたがない短い処理であれば、`std::sync::Mutex` の方が軽い。
+///
+/// ```no_run
+/// use mini_tokio::sync::Mutex;
+/// use mini_tokio::MiniTokio;
+/// use std::sync::Arc;
+///
+/// let mini_tokio = MiniTokio::new();
+///
+/// mini_tokio.block_on(async {
+///     let count = Arc::new(Mutex::new(0));
+///
+///     let handles: Vec<_> = (0..10)
+///         .map(|_| {
+///             let count = count.clone();
+///             mini_tokio::spawn(async move {
+///                 let mut count = count.lock().await;
+///                 *count += 1;
+///             })
+///         })
+///         .collect();
+///
+///     for handle in handles {
+///         handle.await.unwrap();
+///     }
+///
+///     assert_eq!(*count.lock().await, 10);
+/// });
+/// ```
+pub struct Mutex<T: ?Sized> {
+    // 許可が1つだけのセマフォ。許可を獲得したタスクがロックを保持する
+    semaphore: Semaphore,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: `data` には、ロックを保持している1つのタスクからしかアクセスしない
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+/// `Mutex::lock` で得られるガード。破棄されるとロックを解放する
+pub struct MutexGuard<'a, T: ?Sized> {
+    lock: &'a Mutex<T>,
+}
+
+// SAFETY: ガードを共有すると `&T` を複数のスレッドから参照できるため、`T: Sync` が必要
+unsafe impl<T: ?Sized + Sync> Sync for MutexGuard<'_, T> {}
+
+/// `try_lock` でロックを獲得できなかったことを表すエラー
+#[derive(Debug)]
+pub struct TryLockError(pub(super) ());
+
+impl fmt::Display for TryLockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "operation would block")
+    }
+}
+
+impl Error for TryLockError {}
+
+impl<T> Mutex<T> {
+    pub const fn new(value: T) -> Mutex<T> {
+        Mutex {
+            semaphore: Semaphore::new(1),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// `Mutex` を消費して、中の値を取り出す
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// ロックを獲得する
+    ///
+    /// 他のタスクがロックを保持している場合は、解放されるまで待つ。
+    pub async fn lock(&self) -> MutexGuard<'_, T> {
+        // [MEMO]
+        // `Mutex` はセマフォを閉じないので、獲得が失敗することはない
+        self.semaphore
+            .acquire(1)
+            .await
+            .expect("mutex semaphore is never closed");
+
+        MutexGuard { lock: self }
+    }
+
+    /// 待たずにロックの獲得を試みる
+    ///
+    /// ロックを待っているタスクがいる場合も、追い越さずに失敗する。
+    pub fn try_lock(&self) -> Result<MutexGuard<'_, T>, TryLockError> {
+        match self.semaphore.try_acquire(1) {
+            Ok(()) => Ok(MutexGuard { lock: self }),
+            Err(_) => Err(TryLockError(())),
+        }
+    }
+
+    /// 中の値への可変参照を返す
+    ///
+    /// `&mut self` を取るので、ロックを獲得する必要はない。
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Mutex<T> {
+        Mutex::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("Mutex");
+        match self.try_lock() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(_) => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish()
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: ガードが存在する間は、このタスクだけがロックを保持している
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: 同上
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.semaphore.release(1);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for MutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,354 @@
+use crate::sync::linked_list::{LinkedList, Node};
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+/// 値を持たない通知を、待っているタスクに届ける
+///
+/// `notified().await` で通知を待ち、`notify_one` で1つのタスクに、`notify_waiters` で待っている全てのタスクに通知する。
+/// "waker" の保存や更新は `Notify` が行うので、使う側は `Waker` を意識せずに済む。
+///
+/// 待っているタスクがいないときの `notify_one` は、許可（permit）として1つだけ保存される。
+/// 次の `notified().await` は、保存された許可を消費してすぐに完了する。
+///
+/// ```no_run
+/// use mini_tokio::sync::Notify;
+/// use mini_tokio::MiniTokio;
+/// use std::sync::Arc;
+///
+/// let mini_tokio = MiniTokio::new();
+///
+/// mini_tokio.block_on(async {
+///     let notify = Arc::new(Notify::new());
+///     let notify2 = notify.clone();
+///
+///     let handle = mini_tokio::spawn(async move {
+///         notify2.notified().await;
+///         println!("received notification");
+///     });
+///
+///     notify.notify_one();
+///     handle.await.unwrap();
+/// });
+/// ```
+pub struct Notify {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    // 待っているタスクがいないときに `notify_one` された
+    permit: bool,
+    // 通知を待っているタスク
+    waiters: LinkedList<Waiter>,
+    // `notify_waiters` が呼ばれた回数
+    // [MEMO]
+    // `notified()` を呼んだ時点の回数を覚えておき、最初のポーリングまでに回数が増えていれば通知されたとみなす。
+    // まだポーリングされておらず待機リストに入っていない "future" にも、`notify_waiters` が届く。
+    notify_waiters_calls: u64,
+}
+
+/// 通知を待っているタスク
+struct Waiter {
+    waker: Option<Waker>,
+    notified: Option<Notification>,
+}
+
+/// 待っているタスクに届いた通知の種類
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Notification {
+    One,
+    All,
+}
+
+/// `Notified` と `OwnedNotified` の状態
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    // まだポーリングされていない
+    Init,
+    // 待機リストに入って通知を待っている
+    Waiting,
+    // 通知を受け取った
+    Done,
+}
+
+impl Notify {
+    pub const fn new() -> Notify {
+        Notify {
+            inner: Mutex::new(Inner {
+                permit: false,
+                waiters: LinkedList::new(),
+                notify_waiters_calls: 0,
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap()
+    }
+
+    /// 通知を待つ "future" を返す
+    ///
+    /// 返された "future" は、`.await` する前に呼ばれた `notify_waiters` も受け取る。
+    /// 完了する前に破棄された場合、受け取っていた `notify_one` の通知は次に待っているタスクに回される。
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            waiter: NotifiedWaiter::new(self),
+        }
+    }
+
+    /// `notified` と同じだが、`Arc<Notify>` を所有する "future" を返す
+    ///
+    /// `Notify` を借用しないので、構造体のフィールドに保持することができる。
+    pub fn notified_owned(self: Arc<Self>) -> OwnedNotified {
+        let waiter = NotifiedWaiter::new(&self);
+
+        OwnedNotified {
+            notify: self,
+            waiter,
+        }
+    }
+
+    /// 待っているタスクを1つ起こす
+    ///
+    /// 待っているタスクがいなければ許可を保存し、次の `notified().await` をすぐに完了させる。
+    /// 許可は1つまでしか保存されない。
+    pub fn notify_one(&self) {
+        let waker = self.lock().notify_one();
+
+        // ロックを解放してから起こす
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// 現在待っているタスクを全て起こす
+    ///
+    /// 許可は保存されないため、これより後に `notified()` を呼んだタスクは起こされない。
+    pub fn notify_waiters(&self) {
+        let wakers: Vec<_> = {
+            let mut inner = self.lock();
+            inner.notify_waiters_calls += 1;
+
+            std::iter::from_fn(|| inner.waiters.pop_front())
+                .filter_map(|node| {
+                    // SAFETY: リストに入っているノードは有効で、ロックの中でアクセスしている
+                    let waiter = unsafe { &mut (*node.as_ptr()).value };
+                    waiter.notified = Some(Notification::All);
+                    waiter.waker.take()
+                })
+                .collect()
+        };
+
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Notify {
+        Notify::new()
+    }
+}
+
+impl fmt::Debug for Notify {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Notify").finish_non_exhaustive()
+    }
+}
+
+impl Inner {
+    /// 先頭で待っているタスクに通知し、その "waker" を返す。待っているタスクがいなければ許可を保存する
+    fn notify_one(&mut self) -> Option<Waker> {
+        match self.waiters.pop_front() {
+            Some(node) => {
+                // SAFETY: リストに入っているノードは有効で、ロックの中でアクセスしている
+                let waiter = unsafe { &mut (*node.as_ptr()).value };
+                waiter.notified = Some(Notification::One);
+                waiter.waker.take()
+            }
+            None => {
+                self.permit = true;
+                None
+            }
+        }
+    }
+}
+
+/// `Notify::notified` が返す、通知を待つ "future"
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    waiter: NotifiedWaiter,
+}
+
+/// `Notify::notified_owned` が返す、通知を待つ "future"
+pub struct OwnedNotified {
+    notify: Arc<Notify>,
+    waiter: NotifiedWaiter,
+}
+
+/// `Notified` と `OwnedNotified` に共通する、待機リストに入れるノードとその状態
+struct NotifiedWaiter {
+    state: State,
+    // `notified()` を呼んだ時点の `notify_waiters` の呼び出し回数
+    notify_waiters_calls: u64,
+    // 待機リストに入れるノード
+    // [MEMO]
+    // ノードは "future" と一緒にピン留めされるため、リストに入っている間に移動することはない。
+    // リストから参照されている間は、ロックの中で生ポインタを通してのみアクセスする。
+    node: UnsafeCell<Node<Waiter>>,
+}
+
+// SAFETY: `node` には `Notify` のロックの中でしかアクセスしない
+unsafe impl Send for NotifiedWaiter {}
+unsafe impl Sync for NotifiedWaiter {}
+
+impl NotifiedWaiter {
+    fn new(notify: &Notify) -> NotifiedWaiter {
+        NotifiedWaiter {
+            state: State::Init,
+            notify_waiters_calls: notify.lock().notify_waiters_calls,
+            node: UnsafeCell::new(Node::new(Waiter {
+                waker: None,
+                notified: None,
+            })),
+        }
+    }
+
+    /// 通知を受け取っていれば `Ready` を返す。受け取っていなければ "waker" を登録する
+    ///
+    /// `self` はピン留めされていること。
+    fn poll_notified(&mut self, notify: &Notify, cx: &mut Context<'_>) -> Poll<()> {
+        let node = NonNull::new(self.node.get()).unwrap();
+
+        match self.state {
+            State::Init => {
+                let mut inner = notify.lock();
+
+                // `notified()` を呼んだ後に `notify_waiters` が呼ばれていた
+                if inner.notify_waiters_calls != self.notify_waiters_calls {
+                    self.state = State::Done;
+                    return Poll::Ready(());
+                }
+
+                // 保存された許可を消費する
+                if inner.permit {
+                    inner.permit = false;
+                    self.state = State::Done;
+                    return Poll::Ready(());
+                }
+
+                // SAFETY: ノードはピン留めされており、リストから取り除かれるまで `Drop` で破棄されない
+                unsafe {
+                    (*node.as_ptr()).value.waker = Some(cx.waker().clone());
+                    inner.waiters.push_back(node);
+                }
+                self.state = State::Waiting;
+
+                Poll::Pending
+            }
+            State::Waiting => {
+                let _inner = notify.lock();
+
+                // SAFETY: ノードにはロックの中でアクセスしている
+                let waiter = unsafe { &mut (*node.as_ptr()).value };
+
+                // 通知したタスクが、ノードを待機リストから取り除いている
+                if waiter.notified.is_some() {
+                    self.state = State::Done;
+                    return Poll::Ready(());
+                }
+
+                // [MEMO]
+                // "future" が別のタスクにムーブされている可能性があるので、保存している "waker" を更新する。
+                // 同じタスクを起こす "waker" であれば、複製を省く。
+                match &mut waiter.waker {
+                    Some(waker) if waker.will_wake(cx.waker()) => {}
+                    slot => *slot = Some(cx.waker().clone()),
+                }
+
+                Poll::Pending
+            }
+            State::Done => Poll::Ready(()),
+        }
+    }
+
+    /// 待機リストからノードを取り除く
+    ///
+    /// 受け取ったまま消費されなかった `notify_one` の通知は、次に待っているタスクに回す。
+    fn cancel(&mut self, notify: &Notify) {
+        if self.state != State::Waiting {
+            return;
+        }
+
+        let node = NonNull::new(self.node.get()).unwrap();
+
+        let waker = {
+            let mut inner = notify.lock();
+
+            // SAFETY: ノードはこの `Notify` の待機リストにしか入らず、ロックの中でアクセスしている
+            unsafe {
+                inner.waiters.remove(node);
+
+                if (*node.as_ptr()).value.notified == Some(Notification::One) {
+                    inner.notify_one()
+                } else {
+                    None
+                }
+            }
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // SAFETY: `self` を移動させることはない
+        let this = unsafe { self.get_unchecked_mut() };
+        this.waiter.poll_notified(this.notify, cx)
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        self.waiter.cancel(self.notify);
+    }
+}
+
+impl fmt::Debug for Notified<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Notified").finish_non_exhaustive()
+    }
+}
+
+impl Future for OwnedNotified {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // SAFETY: `self` を移動させることはない
+        let this = unsafe { self.get_unchecked_mut() };
+        this.waiter.poll_notified(&this.notify, cx)
+    }
+}
+
+impl Drop for OwnedNotified {
+    fn drop(&mut self) {
+        self.waiter.cancel(&self.notify);
+    }
+}
+
+impl fmt::Debug for OwnedNotified {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedNotified").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,312 @@
+//! 値を1つだけ送信できるチャネル
+//!
+//! 別のタスクに処理を依頼し、その結果を受け取るときに使う。
+//! 受信側は "future" として `.await` でき、送信側が値を送らずに破棄されると `RecvError` を返す。
+//!
+//! ```no_run
+//! use mini_tokio::sync::{mpsc, oneshot};
+//! use mini_tokio::MiniTokio;
+//!
+//! // レスポンスを送り返すための `Sender` をリクエストに含める
+//! type Responder<T> = oneshot::Sender<T>;
+//!
+//! let mini_tokio = MiniTokio::new();
+//!
+//! mini_tokio.block_on(async {
+//!     let (tx, mut rx) = mpsc::channel::<(u32, Responder<u32>)>(32);
+//!
+//!     // リクエストを受け取って処理する "マネージャー" タスク
+//!     let manager = mini_tokio::spawn(async move {
+//!         while let Some((n, resp)) = rx.recv().await {
+//!             // リクエスト側がいなくなっていてもエラーは無視する
+//!             let _ = resp.send(n * 2);
+//!         }
+//!     });
+//!
+//!     let (resp_tx, resp_rx) = oneshot::channel();
+//!     tx.send((21, resp_tx)).await.unwrap();
+//!     assert_eq!(resp_rx.await.unwrap(), 42);
+//!
+//!     drop(tx);
+//!     manager.await.unwrap();
+//! });
+//! ```
+
+use std::fmt;
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+/// 値を1つだけ送信できるチャネルを作る
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let inner = Arc::new(Inner {
+        state: Mutex::new(State {
+            value: None,
+            complete: false,
+            closed: false,
+            rx_waker: None,
+            tx_waker: None,
+        }),
+    });
+
+    (
+        Sender {
+            inner: Some(inner.clone()),
+        },
+        Receiver { inner: Some(inner) },
+    )
+}
+
+/// 値を1つ送信する
+pub struct Sender<T> {
+    // `send` で消費された後は `None` になる
+    inner: Option<Arc<Inner<T>>>,
+}
+
+/// 値を1つ受信する。`.await` すると値が届くまで待つ
+pub struct Receiver<T> {
+    // 値を受け取った後は `None` になる
+    inner: Option<Arc<Inner<T>>>,
+}
+
+struct Inner<T> {
+    state: Mutex<State<T>>,
+}
+
+struct State<T> {
+    // 送信された値。受信側が取り出すまで保持される
+    value: Option<T>,
+    // 送信側が値を送信したか、送信せずに破棄されたかどうか
+    complete: bool,
+    // 受信側が閉じられたか、破棄されたかどうか
+    closed: bool,
+    // 値を待っている受信側の "waker"
+    rx_waker: Option<Waker>,
+    // 受信側が閉じられるのを待っている送信側の "waker"
+    tx_waker: Option<Waker>,
+}
+
+pub mod error {
+    //! `oneshot` の受信が失敗したときのエラー
+
+    use std::error::Error;
+    use std::fmt;
+
+    /// 送信側が値を送信せずに破棄されたか、受信側が閉じられたことを表すエラー
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct RecvError(pub(super) ());
+
+    impl fmt::Display for RecvError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "channel closed")
+        }
+    }
+
+    impl Error for RecvError {}
+
+    /// `try_recv` が失敗したことを表すエラー
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TryRecvError {
+        /// まだ値が送信されていない
+        Empty,
+        /// 送信側が値を送信せずに破棄されたか、受信側が閉じられたか、すでに値を受け取っている
+        Closed,
+    }
+
+    impl fmt::Display for TryRecvError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                TryRecvError::Empty => write!(f, "channel empty"),
+                TryRecvError::Closed => write!(f, "channel closed"),
+            }
+        }
+    }
+
+    impl Error for TryRecvError {}
+}
+
+use error::{RecvError, TryRecvError};
+
+impl<T> Inner<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap()
+    }
+}
+
+/// `slot` の "waker" を `cx` のものに置き換える。同じタスクを起こす "waker" であれば何もしない
+fn register(slot: &mut Option<Waker>, cx: &Context<'_>) {
+    match slot {
+        Some(waker) if waker.will_wake(cx.waker()) => {}
+        slot => *slot = Some(cx.waker().clone()),
+    }
+}
+
+impl<T> Sender<T> {
+    /// 値を送信する
+    ///
+    /// 受信側が閉じられている場合は、送信しようとした値を `Err` で返す。
+    pub fn send(mut self, value: T) -> Result<(), T> {
+        let inner = self.inner.take().unwrap();
+
+        let waker = {
+            let mut state = inner.lock();
+
+            // [MEMO]
+            // `self.inner` はすでに取り出しているので、`Drop` の代わりにここで完了を記録する
+            state.complete = true;
+
+            if state.closed {
+                return Err(value);
+            }
+
+            state.value = Some(value);
+            state.rx_waker.take()
+        };
+
+        // ロックを解放してから受信側を起こす
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+
+    /// 受信側が閉じられるか破棄されるまで待つ
+    ///
+    /// 受信側がいなくなった後の処理を省くために使う。
+    pub async fn closed(&mut self) {
+        future::poll_fn(|cx| self.poll_closed(cx)).await
+    }
+
+    /// 受信側が閉じられているか確認し、閉じられていなければ `cx` の "waker" を登録する
+    pub fn poll_closed(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let inner = self.inner.as_ref().unwrap();
+        let mut state = inner.lock();
+
+        if state.closed {
+            return Poll::Ready(());
+        }
+
+        register(&mut state.tx_waker, cx);
+        Poll::Pending
+    }
+
+    /// 受信側が閉じられているかどうか
+    pub fn is_closed(&self) -> bool {
+        self.inner.as_ref().unwrap().lock().closed
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        // `send` で消費された場合は何もしない
+        let Some(inner) = self.inner.take() else {
+            return;
+        };
+
+        // 値を送信せずに破棄されたことを受信側に知らせる
+        let waker = {
+            let mut state = inner.lock();
+            state.complete = true;
+            state.rx_waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+impl<T> Receiver<T> {
+    /// 待たずに値を受信する
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let Some(inner) = &self.inner else {
+            return Err(TryRecvError::Closed);
+        };
+
+        let result = {
+            let mut state = inner.lock();
+
+            match state.value.take() {
+                Some(value) => Ok(value),
+                None if state.complete || state.closed => Err(TryRecvError::Closed),
+                None => return Err(TryRecvError::Empty),
+            }
+        };
+
+        self.inner = None;
+        result
+    }
+
+    /// チャネルを閉じ、これ以降の送信を失敗させる
+    ///
+    /// すでに送信されていた値は、引き続き受信できる。
+    pub fn close(&mut self) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+
+        let waker = {
+            let mut state = inner.lock();
+            state.closed = true;
+            state.tx_waker.take()
+        };
+
+        // `closed` で待っている送信側を起こす
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<T, RecvError>> {
+        let inner = self
+            .inner
+            .as_ref()
+            .expect("oneshot receiver polled after completion");
+
+        let result = {
+            let mut state = inner.lock();
+
+            match state.value.take() {
+                Some(value) => Ok(value),
+                None if state.complete || state.closed => Err(RecvError(())),
+                None => {
+                    register(&mut state.rx_waker, cx);
+                    return Poll::Pending;
+                }
+            }
+        };
+
+        self.inner = None;
+        Poll::Ready(result)
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.close();
+
+        // 受信されなかった値は、ロックを解放してから破棄する
+        if let Some(inner) = self.inner.take() {
+            let value = inner.lock().value.take();
+            drop(value);
+        }
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
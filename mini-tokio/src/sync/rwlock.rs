@@ -0,0 +1,191 @@
+use crate::sync::batch_semaphore::Semaphore;
+use crate::sync::TryLockError;
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+// 同時に読み込みロックを保持できるタスクの最大数
+// [MEMO]
+// 読み込みはセマフォの許可を1つ、書き込みは全ての許可を獲得する。
+const MAX_READS: usize = u32::MAX as usize >> 3;
+
+/// `.await` をまたいでロックを保持できる、非同期の読み書きロック
+///
+/// 読み込みロックは複数のタスクが同時に保持でき、書き込みロックは1つのタスクだけが保持できる。
+/// ロックは到着順に獲得され、書き込みを待っているタスクがいる間は、後から来た読み込みも待たされる。
+///
+/// ```no_run
+/// use mini_tokio::sync::RwLock;
+/// use mini_tokio::MiniTokio;
+///
+/// let mini_tokio = MiniTokio::new();
+///
+/// mini_tokio.block_on(async {
+///     let lock = RwLock::new(5);
+///
+///     // 読み込みロックは同時に複数保持できる
+///     {
+///         let r1 = lock.read().await;
+///         let r2 = lock.read().await;
+///         assert_eq!(*r1 + *r2, 10);
+///     }
+///
+///     // 書き込みロックは1つだけ
+///     {
+///         let mut w = lock.write().await;
+///         *w += 1;
+///     }
+///
+///     assert_eq!(*lock.read().await, 6);
+/// });
+/// ```
+pub struct RwLock<T: ?Sized> {
+    semaphore: Semaphore,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: 読み込みロックを通して複数のスレッドから `&T` を参照するため、`Sync` には `T: Sync` も必要
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+/// `RwLock::read` で得られるガード。破棄されると読み込みロックを解放する
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+/// `RwLock::write` で得られるガード。破棄されると書き込みロックを解放する
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+// SAFETY: ガードを共有すると `&T` を複数のスレッドから参照できるため、`T: Sync` が必要
+unsafe impl<T: ?Sized + Sync> Sync for RwLockWriteGuard<'_, T> {}
+
+impl<T> RwLock<T> {
+    pub const fn new(value: T) -> RwLock<T> {
+        RwLock {
+            semaphore: Semaphore::new(MAX_READS),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// `RwLock` を消費して、中の値を取り出す
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// 読み込みロックを獲得する
+    ///
+    /// 書き込みロックが保持されているか、書き込みを待っているタスクがいる場合は待つ。
+    pub async fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.semaphore
+            .acquire(1)
+            .await
+            .expect("rwlock semaphore is never closed");
+
+        RwLockReadGuard { lock: self }
+    }
+
+    /// 書き込みロックを獲得する
+    ///
+    /// 他のタスクが読み込みロックか書き込みロックを保持している場合は、全て解放されるまで待つ。
+    pub async fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.semaphore
+            .acquire(MAX_READS)
+            .await
+            .expect("rwlock semaphore is never closed");
+
+        RwLockWriteGuard { lock: self }
+    }
+
+    /// 待たずに読み込みロックの獲得を試みる
+    pub fn try_read(&self) -> Result<RwLockReadGuard<'_, T>, TryLockError> {
+        match self.semaphore.try_acquire(1) {
+            Ok(()) => Ok(RwLockReadGuard { lock: self }),
+            Err(_) => Err(TryLockError(())),
+        }
+    }
+
+    /// 待たずに書き込みロックの獲得を試みる
+    pub fn try_write(&self) -> Result<RwLockWriteGuard<'_, T>, TryLockError> {
+        match self.semaphore.try_acquire(MAX_READS) {
+            Ok(()) => Ok(RwLockWriteGuard { lock: self }),
+            Err(_) => Err(TryLockError(())),
+        }
+    }
+
+    /// 中の値への可変参照を返す
+    ///
+    /// `&mut self` を取るので、ロックを獲得する必要はない。
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> RwLock<T> {
+        RwLock::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("RwLock");
+        match self.try_read() {
+            Ok(guard) => d.field("data", &&*guard),
+            Err(_) => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish()
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: 読み込みロックが保持されている間は、書き込みロックは獲得されない
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.semaphore.release(1);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: 書き込みロックが保持されている間は、このタスクだけがアクセスできる
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: 同上
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.semaphore.release(MAX_READS);
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
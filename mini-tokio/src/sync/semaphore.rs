@@ -0,0 +1,230 @@
+use crate::sync::batch_semaphore::{self, AcquireError, TryAcquireError};
+use std::fmt;
+use std::sync::Arc;
+
+/// 非同期のカウンティングセマフォ
+///
+/// 同時に実行される処理の数を制限するために使う。
+/// 許可（permit）を獲得できたタスクだけが処理を進め、許可が足りないタスクは到着順に待たされる。
+/// 獲得した許可は、`SemaphorePermit` が破棄されるとセマフォに返される。
+///
+/// ```no_run
+/// use mini_tokio::sync::Semaphore;
+/// use mini_tokio::MiniTokio;
+/// use std::sync::Arc;
+///
+/// let mini_tokio = MiniTokio::new();
+///
+/// mini_tokio.block_on(async {
+///     // 同時に処理するのは 3 つまで
+///     let semaphore = Arc::new(Semaphore::new(3));
+///
+///     let handles: Vec<_> = (0..10)
+///         .map(|i| {
+///             let semaphore = semaphore.clone();
+///             mini_tokio::spawn(async move {
+///                 // `OwnedSemaphorePermit` は `Arc<Semaphore>` を所有するので、タスクにムーブできる
+///                 let _permit = semaphore.acquire_owned().await.unwrap();
+///                 println!("processing {}", i);
+///             })
+///         })
+///         .collect();
+///
+///     for handle in handles {
+///         handle.await.unwrap();
+///     }
+/// });
+/// ```
+pub struct Semaphore {
+    inner: batch_semaphore::Semaphore,
+}
+
+/// `Semaphore::acquire` で得られる許可。破棄されると許可をセマフォに返す
+#[must_use]
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+}
+
+/// `Semaphore::acquire_owned` で得られる許可。破棄されると許可をセマフォに返す
+///
+/// `Arc<Semaphore>` を所有するので、`'static` が必要なタスクにムーブできる。
+#[must_use]
+pub struct OwnedSemaphorePermit {
+    semaphore: Arc<Semaphore>,
+    permits: usize,
+}
+
+impl Semaphore {
+    /// `permits` 個の許可をもつセマフォを作る
+    pub const fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            inner: batch_semaphore::Semaphore::new(permits),
+        }
+    }
+
+    /// すぐに獲得できる許可の数を返す
+    pub fn available_permits(&self) -> usize {
+        self.inner.available_permits()
+    }
+
+    /// 許可を `n` 個追加する
+    ///
+    /// 待っているタスクがいれば、先頭から順に必要な数が揃ったタスクを起こす。
+    pub fn add_permits(&self, n: usize) {
+        self.inner.release(n);
+    }
+
+    /// 許可を1つ獲得する
+    ///
+    /// 許可が足りない場合は、他のタスクが許可を返すまで待つ。
+    /// セマフォが閉じられている場合は `AcquireError` を返す。
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, AcquireError> {
+        self.acquire_many(1).await
+    }
+
+    /// 許可を `n` 個まとめて獲得する
+    ///
+    /// `n` 個が揃うまで1つも獲得しない。先に待っているタスクを追い越すこともない。
+    pub async fn acquire_many(&self, n: usize) -> Result<SemaphorePermit<'_>, AcquireError> {
+        self.inner.acquire(n).await?;
+
+        Ok(SemaphorePermit {
+            semaphore: self,
+            permits: n,
+        })
+    }
+
+    /// 待たずに許可を1つ獲得する
+    pub fn try_acquire(&self) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.try_acquire_many(1)
+    }
+
+    /// 待たずに許可を `n` 個獲得する
+    pub fn try_acquire_many(&self, n: usize) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.inner.try_acquire(n)?;
+
+        Ok(SemaphorePermit {
+            semaphore: self,
+            permits: n,
+        })
+    }
+
+    /// 許可を1つ獲得し、`Arc<Semaphore>` を所有する許可を返す
+    pub async fn acquire_owned(self: Arc<Self>) -> Result<OwnedSemaphorePermit, AcquireError> {
+        self.acquire_many_owned(1).await
+    }
+
+    /// 許可を `n` 個まとめて獲得し、`Arc<Semaphore>` を所有する許可を返す
+    pub async fn acquire_many_owned(
+        self: Arc<Self>,
+        n: usize,
+    ) -> Result<OwnedSemaphorePermit, AcquireError> {
+        self.inner.acquire(n).await?;
+
+        Ok(OwnedSemaphorePermit {
+            semaphore: self,
+            permits: n,
+        })
+    }
+
+    /// 待たずに許可を1つ獲得し、`Arc<Semaphore>` を所有する許可を返す
+    pub fn try_acquire_owned(self: Arc<Self>) -> Result<OwnedSemaphorePermit, TryAcquireError> {
+        self.try_acquire_many_owned(1)
+    }
+
+    /// 待たずに許可を `n` 個獲得し、`Arc<Semaphore>` を所有する許可を返す
+    pub fn try_acquire_many_owned(
+        self: Arc<Self>,
+        n: usize,
+    ) -> Result<OwnedSemaphorePermit, TryAcquireError> {
+        self.inner.try_acquire(n)?;
+
+        Ok(OwnedSemaphorePermit {
+            semaphore: self,
+            permits: n,
+        })
+    }
+
+    /// セマフォを閉じる
+    ///
+    /// 待っているタスクは全て起こされて `AcquireError` を返し、これ以降の獲得も失敗する。
+    /// すでに獲得されている許可は、そのまま使い続けられる。
+    pub fn close(&self) {
+        self.inner.close();
+    }
+
+    /// セマフォが閉じられているかどうか
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Semaphore")
+            .field("permits", &self.available_permits())
+            .finish()
+    }
+}
+
+impl SemaphorePermit<'_> {
+    /// 許可をセマフォに返さずに破棄する
+    ///
+    /// セマフォの許可の総数は、その分だけ減る。
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+
+    /// この許可が表す許可の数を返す
+    pub fn num_permits(&self) -> usize {
+        self.permits
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.semaphore.add_permits(self.permits);
+    }
+}
+
+impl fmt::Debug for SemaphorePermit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SemaphorePermit")
+            .field("permits", &self.permits)
+            .finish()
+    }
+}
+
+impl OwnedSemaphorePermit {
+    /// 許可をセマフォに返さずに破棄する
+    ///
+    /// セマフォの許可の総数は、その分だけ減る。
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+
+    /// この許可が表す許可の数を返す
+    pub fn num_permits(&self) -> usize {
+        self.permits
+    }
+
+    /// この許可を獲得した `Semaphore` を返す
+    pub fn semaphore(&self) -> &Arc<Semaphore> {
+        &self.semaphore
+    }
+}
+
+impl Drop for OwnedSemaphorePermit {
+    fn drop(&mut self) {
+        self.semaphore.add_permits(self.permits);
+    }
+}
+
+impl fmt::Debug for OwnedSemaphorePermit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedSemaphorePermit")
+            .field("permits", &self.permits)
+            .finish()
+    }
+}
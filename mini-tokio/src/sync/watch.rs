@@ -0,0 +1,416 @@
+//! 最新の値だけを保持し、値が変わったことを受信側に知らせるチャネル
+//!
+//! 設定の更新のように、途中の値を取りこぼしても最新の値さえ分かればよい場合に使う。
+//! 受信側は `changed` で値の更新を待ち、`borrow_and_update` で最新の値を参照する。
+//!
+//! ```no_run
+//! use mini_tokio::sync::watch;
+//! use mini_tokio::MiniTokio;
+//!
+//! let mini_tokio = MiniTokio::new();
+//!
+//! mini_tokio.block_on(async {
+//!     let (tx, mut rx) = watch::channel("hello");
+//!
+//!     let handle = mini_tokio::spawn(async move {
+//!         // 送信側が破棄されるまで、値が更新されるたびに表示する
+//!         while rx.changed().await.is_ok() {
+//!             println!("received = {}", *rx.borrow_and_update());
+//!         }
+//!     });
+//!
+//!     tx.send("world").unwrap();
+//!     drop(tx);
+//!     handle.await.unwrap();
+//! });
+//! ```
+
+use crate::sync::linked_list::{LinkedList, Node};
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::future::Future;
+use std::ops::Deref;
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard};
+use std::task::{Context, Poll, Waker};
+
+/// `init` を初期値とするチャネルを作る
+pub fn channel<T>(init: T) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        value: RwLock::new(init),
+        state: Mutex::new(State {
+            version: 0,
+            closed: false,
+            rx_count: 1,
+            waiters: LinkedList::new(),
+        }),
+    });
+
+    let rx = Receiver {
+        shared: shared.clone(),
+        version: 0,
+    };
+
+    (Sender { shared }, rx)
+}
+
+/// 値を更新する
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// 最新の値を参照し、値の更新を待つ
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    // 最後に確認した値のバージョン
+    version: u64,
+}
+
+/// `borrow` で得られる、値への参照
+///
+/// 参照している間は送信側が値を更新できないため、`.await` をまたいで保持しないこと。
+pub struct Ref<'a, T> {
+    inner: RwLockReadGuard<'a, T>,
+    has_changed: bool,
+}
+
+struct Shared<T> {
+    value: RwLock<T>,
+    state: Mutex<State>,
+}
+
+struct State {
+    // 値が更新されるたびに増える
+    // [MEMO]
+    // 送信側は `value` の書き込みロックを取ったままバージョンを増やす。
+    // 受信側は読み込みロックを取ってからバージョンを読むので、値とバージョンの組み合わせがずれることはない。
+    version: u64,
+    // 送信側が破棄されたかどうか
+    closed: bool,
+    // 破棄されていない `Receiver` の数
+    rx_count: usize,
+    // 値の更新を待っている受信側
+    waiters: LinkedList<Option<Waker>>,
+}
+
+pub mod error {
+    //! `watch` の操作が失敗したときのエラー
+
+    use std::error::Error;
+    use std::fmt;
+
+    /// 受信側が1つもなく、値を送信できなかったことを表すエラー
+    ///
+    /// 送信しようとした値を含んでいる。
+    pub struct SendError<T>(pub T);
+
+    impl<T> fmt::Debug for SendError<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("SendError").finish_non_exhaustive()
+        }
+    }
+
+    impl<T> fmt::Display for SendError<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "channel closed")
+        }
+    }
+
+    impl<T> Error for SendError<T> {}
+
+    /// 送信側が破棄され、これ以上値が更新されないことを表すエラー
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct RecvError(pub(super) ());
+
+    impl fmt::Display for RecvError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "channel closed")
+        }
+    }
+
+    impl Error for RecvError {}
+}
+
+use error::{RecvError, SendError};
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl State {
+    /// 待っている受信側を全て取り除き、その "waker" を返す
+    ///
+    /// "waker" の呼び出しはロックを解放してから行うこと。
+    fn take_waiters(&mut self) -> Vec<Waker> {
+        std::iter::from_fn(|| self.waiters.pop_front())
+            .filter_map(|node| {
+                // SAFETY: リストに入っているノードは有効で、ロックの中でアクセスしている
+                unsafe { (*node.as_ptr()).value.take() }
+            })
+            .collect()
+    }
+}
+
+impl<T> Sender<T> {
+    /// 値を更新し、受信側に知らせる
+    ///
+    /// 受信側が1つもない場合は値を更新せず、送信しようとした値を含む `SendError` を返す。
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        if self.shared.lock().rx_count == 0 {
+            return Err(SendError(value));
+        }
+
+        self.send_replace(value);
+        Ok(())
+    }
+
+    /// 受信側の有無にかかわらず値を更新し、更新前の値を返す
+    pub fn send_replace(&self, value: T) -> T {
+        let (old, wakers) = {
+            let mut current = self.shared.value.write().unwrap();
+            let old = std::mem::replace(&mut *current, value);
+
+            let mut state = self.shared.lock();
+            state.version += 1;
+
+            (old, state.take_waiters())
+        };
+
+        for waker in wakers {
+            waker.wake();
+        }
+
+        old
+    }
+
+    /// 現在の値を参照する
+    pub fn borrow(&self) -> Ref<'_, T> {
+        Ref {
+            inner: self.shared.value.read().unwrap(),
+            has_changed: false,
+        }
+    }
+
+    /// 新しい受信側を作る
+    ///
+    /// 作られた受信側は、現在の値を確認済みとして扱う。
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut state = self.shared.lock();
+        state.rx_count += 1;
+
+        Receiver {
+            shared: self.shared.clone(),
+            version: state.version,
+        }
+    }
+
+    /// 受信側の数を返す
+    pub fn receiver_count(&self) -> usize {
+        self.shared.lock().rx_count
+    }
+
+    /// 受信側が全て破棄されているかどうか
+    pub fn is_closed(&self) -> bool {
+        self.receiver_count() == 0
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let wakers = {
+            let mut state = self.shared.lock();
+            state.closed = true;
+            state.take_waiters()
+        };
+
+        // 更新を待っている受信側を起こして、`RecvError` を返させる
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+impl<T> Receiver<T> {
+    /// 現在の値を参照する。値は確認済みにならない
+    pub fn borrow(&self) -> Ref<'_, T> {
+        let inner = self.shared.value.read().unwrap();
+        let version = self.shared.lock().version;
+
+        Ref {
+            inner,
+            has_changed: version != self.version,
+        }
+    }
+
+    /// 現在の値を参照し、確認済みにする
+    ///
+    /// 次の `changed` は、これより後に値が更新されるまで待つ。
+    pub fn borrow_and_update(&mut self) -> Ref<'_, T> {
+        let inner = self.shared.value.read().unwrap();
+        let version = self.shared.lock().version;
+
+        let has_changed = version != self.version;
+        self.version = version;
+
+        Ref { inner, has_changed }
+    }
+
+    /// 最後に確認した後で値が更新されたかどうか
+    ///
+    /// 送信側が破棄されている場合は `RecvError` を返す。
+    pub fn has_changed(&self) -> Result<bool, RecvError> {
+        let state = self.shared.lock();
+
+        if state.closed {
+            return Err(RecvError(()));
+        }
+
+        Ok(state.version != self.version)
+    }
+
+    /// 値が更新されるまで待ち、更新された値を確認済みにする
+    ///
+    /// 最後に確認した後ですでに更新されていれば、すぐに返る。
+    /// 送信側が破棄されている場合は `RecvError` を返す。
+    pub async fn changed(&mut self) -> Result<(), RecvError> {
+        Changed::new(self).await
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Receiver<T> {
+        self.shared.lock().rx_count += 1;
+
+        Receiver {
+            shared: self.shared.clone(),
+            version: self.version,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.lock().rx_count -= 1;
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+impl<T> Ref<'_, T> {
+    /// 受信側が最後に確認した後で、値が更新されていたかどうか
+    ///
+    /// 送信側の `borrow` で得た場合は常に `false` を返す。
+    pub fn has_changed(&self) -> bool {
+        self.has_changed
+    }
+}
+
+impl<T> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Ref<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// 値が更新されるまで待つ "future"
+///
+/// 更新されていない場合は、自身のノードを待機リストに入れて `Pending` を返す。
+/// 扱いは `broadcast` の `Recv` と同じ。
+struct Changed<'a, T> {
+    receiver: &'a mut Receiver<T>,
+    node: UnsafeCell<Node<Option<Waker>>>,
+    // 一度でも待機リストに入ったかどうか
+    queued: bool,
+}
+
+// SAFETY: `node` にはチャネルのロックの中でしかアクセスしない
+unsafe impl<T: Send + Sync> Send for Changed<'_, T> {}
+
+impl<'a, T> Changed<'a, T> {
+    fn new(receiver: &'a mut Receiver<T>) -> Changed<'a, T> {
+        Changed {
+            receiver,
+            node: UnsafeCell::new(Node::new(None)),
+            queued: false,
+        }
+    }
+}
+
+impl<T> Future for Changed<'_, T> {
+    type Output = Result<(), RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), RecvError>> {
+        // SAFETY: `self` を移動させることはない
+        let this = unsafe { self.get_unchecked_mut() };
+        let node = NonNull::new(this.node.get()).unwrap();
+
+        let mut state = this.receiver.shared.lock();
+
+        // [MEMO]
+        // 送信側が破棄される前に更新された値があれば、先にそちらを知らせる
+        if state.version != this.receiver.version || state.closed {
+            // SAFETY: ノードはこのチャネルの待機リストにしか入らない
+            unsafe { state.waiters.remove(node) };
+
+            if state.version != this.receiver.version {
+                this.receiver.version = state.version;
+                return Poll::Ready(Ok(()));
+            }
+            return Poll::Ready(Err(RecvError(())));
+        }
+
+        // SAFETY: ノードにはロックの中でアクセスしている
+        let (linked, waker) = unsafe {
+            let node = &mut *node.as_ptr();
+            (node.is_linked(), &mut node.value)
+        };
+
+        match waker {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            waker => *waker = Some(cx.waker().clone()),
+        }
+
+        if !linked {
+            // SAFETY: ノードはピン留めされており、リストから取り除かれるまで `Drop` で破棄されない
+            unsafe { state.waiters.push_back(node) };
+            this.queued = true;
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Changed<'_, T> {
+    fn drop(&mut self) {
+        // 一度も待機リストに入っていなければ、チャネルから参照されていることはない
+        if !self.queued {
+            return;
+        }
+
+        let node = NonNull::new(self.node.get()).unwrap();
+        let mut state = self.receiver.shared.lock();
+
+        // SAFETY: ノードはこのチャネルの待機リストにしか入らず、ロックの中でアクセスしている
+        unsafe { state.waiters.remove(node) };
+    }
+}
@@ -0,0 +1,33 @@
+use crate::task::Task;
+use std::fmt;
+use std::sync::Arc;
+
+/// タスクをキャンセルするためのハンドル
+///
+/// `JoinHandle::abort_handle` で取得する。`JoinHandle` と違い、タスクの出力を受け取ることはできないが、
+/// 複製して複数の場所からキャンセルすることができる。
+#[derive(Clone)]
+pub struct AbortHandle {
+    task: Arc<Task>,
+}
+
+impl AbortHandle {
+    pub(super) fn new(task: Arc<Task>) -> AbortHandle {
+        AbortHandle { task }
+    }
+
+    /// タスクをキャンセルする
+    ///
+    /// 詳細は `JoinHandle::abort` を参照。
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+}
+
+impl fmt::Debug for AbortHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AbortHandle")
+            .field("id", &self.task.id())
+            .finish()
+    }
+}
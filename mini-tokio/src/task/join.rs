@@ -0,0 +1,286 @@
+use crate::task::{AbortHandle, Task};
+use std::any::Any;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// タスクの出力を書き込む `Output` と、`JoinHandle` が出力を受け取るための `JoinState` の組を作る
+pub(super) fn joinable<T>() -> (Output<T>, Arc<JoinState<T>>) {
+    let state = Arc::new(JoinState::new());
+    let output = Output {
+        state: Some(state.clone()),
+    };
+
+    (output, state)
+}
+
+/// タスクと `JoinHandle` の間で共有される、タスクの出力の置き場所
+pub(super) struct JoinState<T> {
+    inner: Mutex<JoinInner<T>>,
+}
+
+struct JoinInner<T> {
+    // タスクの出力。`JoinHandle` が取り出すまで保持される
+    output: Option<Result<T, JoinError>>,
+    // 出力を待っている `JoinHandle` の "waker"
+    waker: Option<Waker>,
+    // 出力がすでに `JoinHandle` によって取り出されたかどうか
+    taken: bool,
+}
+
+impl<T> JoinState<T> {
+    fn new() -> JoinState<T> {
+        JoinState {
+            inner: Mutex::new(JoinInner {
+                output: None,
+                waker: None,
+                taken: false,
+            }),
+        }
+    }
+
+    fn complete(&self, output: Result<T, JoinError>) {
+        let waker = {
+            let mut inner = self.inner.lock().unwrap();
+
+            // 最初に書き込まれた結果だけを残す
+            // [MEMO]
+            // `Output` と `Task::poll`（パニックの書き込み）の2か所から書き込まれる可能性があるため。
+            if inner.output.is_some() || inner.taken {
+                return;
+            }
+
+            inner.output = Some(output);
+            inner.waker.take()
+        };
+
+        // ロックを解放してから "waker" を呼び出す
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// 出力の型 `T` を消して、`Task` から `JoinState` にパニックを書き込むためのトレイト
+pub(super) trait JoinPanic: Send + Sync {
+    fn set_panic(&self, payload: Box<dyn Any + Send>);
+}
+
+impl<T: Send> JoinPanic for JoinState<T> {
+    fn set_panic(&self, payload: Box<dyn Any + Send>) {
+        self.complete(Err(JoinError::panic(payload)));
+    }
+}
+
+/// タスクの出力を `JoinState` に書き込むためのハンドル
+///
+/// 出力を書き込む前に破棄された場合は、タスクが途中で破棄されたものとして `JoinError` を書き込む。
+pub(super) struct Output<T> {
+    state: Option<Arc<JoinState<T>>>,
+}
+
+impl<T> Output<T> {
+    pub(super) fn complete(mut self, output: Result<T, JoinError>) {
+        if let Some(state) = self.state.take() {
+            state.complete(output);
+        }
+    }
+}
+
+impl<T> Drop for Output<T> {
+    fn drop(&mut self) {
+        // 完了する前にランタイムから破棄されたので、キャンセル扱いとする
+        if let Some(state) = self.state.take() {
+            state.complete(Err(JoinError::cancelled()));
+        }
+    }
+}
+
+/// spawn したタスクの完了を待つためのハンドル
+///
+/// `JoinHandle` 自体が "future" であり、`.await` することでタスクの出力を受け取ることができる。
+/// タスクがパニックした場合やキャンセルされた場合は `JoinError` が返る。
+/// `JoinHandle` を破棄してもタスクはキャンセルされず、そのまま実行される。
+pub struct JoinHandle<T> {
+    state: Arc<JoinState<T>>,
+    // `abort` でキャンセルするためのタスクへの参照
+    task: Arc<Task>,
+}
+
+impl<T> JoinHandle<T> {
+    pub(super) fn new(state: Arc<JoinState<T>>, task: Arc<Task>) -> JoinHandle<T> {
+        JoinHandle { state, task }
+    }
+
+    /// タスクをキャンセルする
+    ///
+    /// タスクの "future" は、次にワーカーがタスクを取り出したときにポーリングされずに破棄される。
+    /// キャンセルされたタスクの `JoinHandle` は `JoinError::is_cancelled` が `true` のエラーを返す。
+    /// すでに完了しているタスクに対しては何もしない。
+    pub fn abort(&self) {
+        self.task.abort();
+    }
+
+    /// `JoinHandle` とは独立してタスクをキャンセルできる `AbortHandle` を返す
+    pub fn abort_handle(&self) -> AbortHandle {
+        AbortHandle::new(self.task.clone())
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut inner = self.state.inner.lock().unwrap();
+
+        if let Some(output) = inner.output.take() {
+            inner.taken = true;
+            return Poll::Ready(output);
+        }
+
+        assert!(!inner.taken, "`JoinHandle` polled after completion");
+
+        // `Delay` と同様に、"waker" が別のタスクのものに変わっていれば更新する
+        match &inner.waker {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            _ => inner.waker = Some(cx.waker().clone()),
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<T> fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinHandle").finish_non_exhaustive()
+    }
+}
+
+/// タスクが出力を返さずに終了したことを表すエラー
+pub struct JoinError {
+    repr: Repr,
+}
+
+enum Repr {
+    Cancelled,
+    // [MEMO]
+    // パニックのペイロードは `Send` だが `Sync` ではないため、`Mutex` で包んで `JoinError` を `Sync` にしている。
+    Panic(Mutex<Box<dyn Any + Send + 'static>>),
+}
+
+impl JoinError {
+    fn cancelled() -> JoinError {
+        JoinError {
+            repr: Repr::Cancelled,
+        }
+    }
+
+    fn panic(payload: Box<dyn Any + Send>) -> JoinError {
+        JoinError {
+            repr: Repr::Panic(Mutex::new(payload)),
+        }
+    }
+
+    /// タスクがキャンセルされた場合に `true` を返す
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self.repr, Repr::Cancelled)
+    }
+
+    /// タスクがパニックした場合に `true` を返す
+    pub fn is_panic(&self) -> bool {
+        matches!(self.repr, Repr::Panic(_))
+    }
+
+    /// タスクのパニックのペイロードを取り出す
+    ///
+    /// `std::panic::resume_unwind` に渡すと、タスクのパニックを呼び出し側で再開できる。
+    ///
+    /// # Panics
+    ///
+    /// タスクがパニックしていない場合はパニックする。
+    pub fn into_panic(self) -> Box<dyn Any + Send + 'static> {
+        self.try_into_panic()
+            .expect("`JoinError` reason is not a panic.")
+    }
+
+    /// タスクがパニックしていればペイロードを、そうでなければ `self` を返す
+    pub fn try_into_panic(self) -> Result<Box<dyn Any + Send + 'static>, JoinError> {
+        match self.repr {
+            Repr::Panic(payload) => Ok(payload.into_inner().unwrap_or_else(|e| e.into_inner())),
+            _ => Err(self),
+        }
+    }
+
+    /// パニックのペイロードが文字列であれば、そのメッセージを返す
+    fn panic_message(&self) -> Option<String> {
+        let Repr::Panic(payload) = &self.repr else {
+            return None;
+        };
+        let payload = payload.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(message) = payload.downcast_ref::<&'static str>() {
+            Some(message.to_string())
+        } else {
+            payload.downcast_ref::<String>().cloned()
+        }
+    }
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.repr, self.panic_message()) {
+            (Repr::Cancelled, _) => write!(f, "task was cancelled"),
+            (Repr::Panic(_), Some(message)) => {
+                write!(f, "task panicked with message {:?}", message)
+            }
+            (Repr::Panic(_), None) => write!(f, "task panicked"),
+        }
+    }
+}
+
+impl fmt::Debug for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.repr, self.panic_message()) {
+            (Repr::Cancelled, _) => write!(f, "JoinError::Cancelled"),
+            (Repr::Panic(_), Some(message)) => write!(f, "JoinError::Panic({:?}, ...)", message),
+            (Repr::Panic(_), None) => write!(f, "JoinError::Panic(...)"),
+        }
+    }
+}
+
+impl std::error::Error for JoinError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::MiniTokio;
+    use std::future::pending;
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[test]
+    fn cancelled_when_runtime_dropped_during_unwinding() {
+        let mini_tokio = MiniTokio::new();
+        let handle = mini_tokio.spawn(pending::<()>());
+
+        // パニックの巻き戻しの途中で `MiniTokio` が破棄され、`shutdown` が呼ばれる
+        let res = panic::catch_unwind(AssertUnwindSafe(move || {
+            let _mini_tokio = mini_tokio;
+            panic!("unwinding");
+        }));
+        assert!(res.is_err());
+
+        let res = MiniTokio::new().block_on(handle);
+        assert!(res.unwrap_err().is_cancelled());
+    }
+
+    #[test]
+    fn panic_is_reported() {
+        let mini_tokio = MiniTokio::new();
+
+        let res = mini_tokio.block_on(async { crate::spawn(async { panic!("boom") }).await });
+        let err = res.unwrap_err();
+        assert!(err.is_panic());
+        assert_eq!(*err.into_panic().downcast::<&str>().unwrap(), "boom");
+    }
+}
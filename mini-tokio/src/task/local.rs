@@ -0,0 +1,228 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+
+/// `task_local!` で宣言する、タスクごとの値を保持するキー
+///
+/// 値は `scope` に渡した "future" の中からだけ参照できる。
+/// タスクが別のワーカースレッドに移っても、同じ値を参照できる。
+///
+/// [MEMO]
+/// 値そのものは "future" と一緒に `TaskLocalFuture` が持ち、ポーリングしている間だけスレッドローカルに移す。
+/// ポーリングはどのワーカーでも1つのスレッドの中で完結するので、タスクがスレッドを移っても値がついていく。
+pub struct LocalKey<T: 'static> {
+    #[doc(hidden)]
+    pub inner: thread::LocalKey<RefCell<Option<T>>>,
+}
+
+impl<T: 'static> LocalKey<T> {
+    /// `value` を設定した状態で `future` を実行する "future" を返す
+    ///
+    /// 返された "future" をポーリングしている間だけ、`with` や `get` で `value` を参照できる。
+    ///
+    /// ```no_run
+    /// use mini_tokio::MiniTokio;
+    ///
+    /// mini_tokio::task_local! {
+    ///     static REQUEST_ID: u64;
+    /// }
+    ///
+    /// let mini_tokio = MiniTokio::new();
+    ///
+    /// mini_tokio.block_on(async {
+    ///     let handle = mini_tokio::spawn(REQUEST_ID.scope(42, async {
+    ///         assert_eq!(REQUEST_ID.get(), 42);
+    ///     }));
+    ///     handle.await.unwrap();
+    /// });
+    /// ```
+    pub fn scope<F: Future>(&'static self, value: T, future: F) -> TaskLocalFuture<T, F> {
+        TaskLocalFuture {
+            local: self,
+            slot: Some(value),
+            future: Some(future),
+        }
+    }
+
+    /// `value` を設定した状態で `f` を呼び出す
+    pub fn sync_scope<F, R>(&'static self, value: T, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let mut slot = Some(value);
+        self.scope_inner(&mut slot, f)
+    }
+
+    /// 設定されている値への参照を `f` に渡して呼び出す
+    ///
+    /// # Panics
+    ///
+    /// `scope` や `sync_scope` の外から呼び出された場合はパニックする。
+    pub fn with<F, R>(&'static self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        match self.try_with(f) {
+            Ok(res) => res,
+            Err(_) => panic!("cannot access a task-local storage value without setting it first"),
+        }
+    }
+
+    /// 設定されている値への参照を `f` に渡して呼び出す
+    ///
+    /// `scope` や `sync_scope` の外から呼び出された場合は `AccessError` を返す。
+    pub fn try_with<F, R>(&'static self, f: F) -> Result<R, AccessError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        // [MEMO]
+        // スレッドの終了時にスレッドローカルが破棄された後も、値が設定されていないものとして扱う。
+        let res = self.inner.try_with(|slot| slot.borrow().as_ref().map(f));
+
+        match res {
+            Ok(Some(res)) => Ok(res),
+            Ok(None) | Err(_) => Err(AccessError(())),
+        }
+    }
+
+    /// `slot` の値をスレッドローカルに移してから `f` を呼び出し、終わったら `slot` に戻す
+    ///
+    /// `f` がパニックした場合も値は戻される。
+    fn scope_inner<F, R>(&'static self, slot: &mut Option<T>, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        struct Guard<'a, T: 'static> {
+            local: &'static LocalKey<T>,
+            slot: &'a mut Option<T>,
+        }
+
+        impl<T: 'static> Drop for Guard<'_, T> {
+            fn drop(&mut self) {
+                // `scope_inner` で移せたので、ここでは失敗しない
+                self.local.inner.with(|inner| {
+                    mem::swap(self.slot, &mut *inner.borrow_mut());
+                });
+            }
+        }
+
+        // [MEMO]
+        // スコープが入れ子になった場合は、外側の値を `slot` に預かり、内側のスコープを抜けたときに元に戻す。
+        let swapped = self.inner.try_with(|inner| {
+            inner
+                .try_borrow_mut()
+                .map(|mut inner| mem::swap(slot, &mut *inner))
+        });
+
+        match swapped {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => panic!("cannot enter a task-local scope while the task-local storage is borrowed"),
+            Err(_) => panic!(
+                "cannot enter a task-local scope during or after destruction of the underlying thread-local"
+            ),
+        }
+
+        let _guard = Guard { local: self, slot };
+        f()
+    }
+}
+
+impl<T: Clone + 'static> LocalKey<T> {
+    /// 設定されている値の複製を返す
+    ///
+    /// # Panics
+    ///
+    /// `scope` や `sync_scope` の外から呼び出された場合はパニックする。
+    pub fn get(&'static self) -> T {
+        self.with(T::clone)
+    }
+}
+
+impl<T: 'static> fmt::Debug for LocalKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("LocalKey { .. }")
+    }
+}
+
+/// `LocalKey::scope` が返す "future"
+///
+/// ポーリングするたびに値を設定してから、包んでいる "future" をポーリングする。
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct TaskLocalFuture<T: 'static, F> {
+    local: &'static LocalKey<T>,
+    // ポーリングしていない間、値を預かっておく場所
+    slot: Option<T>,
+    // 完了したら `None` になる
+    future: Option<F>,
+}
+
+impl<T: 'static, F: Future> Future for TaskLocalFuture<T, F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // SAFETY: `future` はピン留めしたまま扱い、完了するか破棄するまで `self` から移動させない
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        this.local.scope_inner(&mut this.slot, || {
+            let res = match future.as_mut().as_pin_mut() {
+                Some(fut) => fut.poll(cx),
+                None => panic!("`TaskLocalFuture` polled after completion"),
+            };
+
+            // 完了した "future" は、値を参照できるうちに破棄する
+            if res.is_ready() {
+                future.set(None);
+            }
+
+            res
+        })
+    }
+}
+
+impl<T: 'static, F> Drop for TaskLocalFuture<T, F> {
+    fn drop(&mut self) {
+        // [MEMO]
+        // 完了する前に破棄された場合（`abort` やランタイムの停止など）も、"future" の `Drop` から値を参照できるよう、
+        // 値を設定してから "future" を破棄する。スコープに入れない場合は、そのまま破棄する。
+        if self.future.is_none() {
+            return;
+        }
+
+        // SAFETY: `future` はその場で破棄するだけで、移動させない
+        let mut future = unsafe { Pin::new_unchecked(&mut self.future) };
+        let local = self.local;
+
+        if matches!(
+            local.inner.try_with(|inner| inner.try_borrow_mut().is_ok()),
+            Ok(true)
+        ) {
+            local.scope_inner(&mut self.slot, || future.set(None));
+        }
+    }
+}
+
+impl<T: fmt::Debug + 'static, F> fmt::Debug for TaskLocalFuture<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskLocalFuture")
+            .field("value", &self.slot)
+            .finish()
+    }
+}
+
+/// `task_local!` の値が設定されていないことを表すエラー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccessError(());
+
+impl fmt::Display for AccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task-local value not set")
+    }
+}
+
+impl Error for AccessError {}
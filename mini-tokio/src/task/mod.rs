@@ -0,0 +1,549 @@
+use crate::runtime::Handle;
+use std::cell::UnsafeCell;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+mod abort;
+pub use abort::AbortHandle;
+
+mod join;
+use join::JoinPanic;
+pub use join::{JoinError, JoinHandle};
+
+mod local;
+pub use local::{AccessError, LocalKey, TaskLocalFuture};
+
+mod state;
+use state::State;
+
+mod waker;
+
+/// 現在のランタイムに新しいタスクを spawn する
+///
+/// タスクの中など、ランタイムのコンテキストから呼び出す必要がある。
+/// `MiniTokio` への参照を持たないタスクからでも、別のタスクを spawn できる。
+///
+/// # Panics
+///
+/// ランタイムの外から呼び出された場合はパニックする。
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    Task::spawn(future, &Handle::current())
+}
+
+/// ブロッキングする処理を、ワーカーとは別のスレッドで実行する
+///
+/// ファイルの読み書きや同期的なライブラリの呼び出しなど、ワーカーを長時間止めてしまう処理に使う。
+/// `f` はランタイムが管理するブロッキングスレッドのプールで実行され、
+/// 完了すると戻り値の `JoinHandle` を待っているタスクが起こされる。
+///
+/// 実行が始まる前であれば `JoinHandle::abort` でキャンセルできる。
+/// 実行が始まった後は、`f` が返るまで止めることはできない。
+///
+/// # Panics
+///
+/// ランタイムの外から呼び出された場合はパニックする。
+///
+/// # Examples
+///
+/// ```no_run
+/// use mini_tokio::MiniTokio;
+///
+/// let mini_tokio = MiniTokio::new();
+///
+/// let sum = mini_tokio.block_on(async {
+///     let handle = mini_tokio::spawn_blocking(|| (1..=100u64).sum::<u64>());
+///     handle.await.unwrap()
+/// });
+///
+/// assert_eq!(sum, 5050);
+/// ```
+pub fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    Task::spawn_blocking(f, &Handle::current())
+}
+
+pub(crate) struct Task {
+    // タスクの状態
+    // [MEMO]
+    // 以前は `future` を `Mutex` で保護していたが、完了したタスクが再度ポーリングされたり、
+    // 何度も `wake` されたタスクが同じ数だけキューに積まれたりしていた。
+    // 状態をアトミックに管理し、`RUNNING` に遷移させたスレッドだけが `future` にアクセスするようにしている。
+    state: State,
+    // [MEMO]
+    // `Pin<T>`は、ある値のメモリアドレスを固定（ピン留め）することで、自己参照構造体の安全性を保証する仕組み
+    // [MEMO]
+    // 出力の型はタスクごとに異なるため、ここでは `Output = ()` に揃えた "future" を保持する。
+    // 本来の出力は `spawn` でラップした "future" が `JoinHandle` と共有する `JoinState` に書き込む。
+    // [MEMO]
+    // 完了したタスクや、ランタイムの停止で破棄されたタスクは `None` になる。
+    future: UnsafeCell<Option<Pin<Box<dyn Future<Output = ()> + Send>>>>,
+    scheduler: Handle,
+    // "future" がパニックしたときに、`JoinHandle` にパニックを届けるための参照
+    join: Arc<dyn JoinPanic>,
+    // ランタイムがタスクを管理するための識別子
+    id: u64,
+}
+
+// SAFETY: `future` には、`state` を `RUNNING` に遷移させた1つのスレッドからしかアクセスしない
+unsafe impl Sync for Task {}
+
+impl Task {
+    /// "waker" が呼ばれたときに、タスクを再度スケジュールする
+    ///
+    /// [MEMO]
+    /// 以前は `futures` クレートの `ArcWake` を実装して "waker" を作っていた。
+    /// 今は `waker` モジュールの `RawWakerVTable` から呼ばれる。
+    fn schedule(self: &Arc<Self>) {
+        // すでにキューに入っているタスクや、完了したタスクはキューに入れない
+        if self.state.transition_to_scheduled() {
+            // [MEMO]
+            // ワーカーの中から呼ばれた場合（ポーリング中の `wake` など）は、そのワーカーのローカルキューにプッシュされる。
+            self.scheduler.schedule(self.clone());
+        }
+    }
+
+    pub(crate) fn poll(self: Arc<Self>) {
+        // 完了したタスクや、停止時に破棄されたタスクはポーリングしない
+        if !self.state.transition_to_running() {
+            return;
+        }
+
+        // `abort` されたタスクはポーリングせずに "future" を破棄する
+        // [MEMO]
+        // "future" を破棄すると `Output` の `Drop` が呼ばれ、`JoinHandle` にはキャンセルを表すエラーが届く。
+        if self.state.is_cancelled() {
+            self.complete();
+            return;
+        }
+
+        // `Task` インスタンスから "waker" を生成する
+        let waker = waker::waker_ref(&self);
+        let mut cx: Context<'_> = Context::from_waker(&waker);
+
+        // SAFETY: `RUNNING` に遷移させたのはこのスレッドなので、`future` に排他的にアクセスできる
+        let Some(future) = (unsafe { &mut *self.future.get() }) else {
+            self.complete();
+            return;
+        };
+
+        // "future" をポーリングする
+        // [MEMO]
+        // こちらは`Future` トレイトのpollメソッドを呼び出している
+        // [MEMO]
+        // "future" のパニックがワーカーのループまで巻き戻ると、ランタイム全体が停止してしまう。
+        // `catch_unwind` でパニックを捕まえ、そのタスクだけを失敗させる。
+        let res = panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(&mut cx)));
+
+        match res {
+            Ok(Poll::Ready(())) => {
+                self.complete();
+                return;
+            }
+            Ok(Poll::Pending) if self.scheduler.is_shutdown() => {
+                self.complete();
+                return;
+            }
+            Ok(Poll::Pending) => {}
+            Err(payload) => {
+                // パニックのペイロードを `JoinHandle` に届けてから、"future" を破棄する
+                self.join.set_panic(payload);
+                self.complete();
+                self.scheduler.unhandled_panic();
+                return;
+            }
+        }
+
+        if !self.state.transition_to_idle() {
+            // ポーリング中に `wake` された
+            // [MEMO]
+            // `wake` が何度呼ばれていても、ここでキューに入れ直すのは1回だけである。
+            if self.scheduler.is_shutdown() {
+                self.complete();
+            } else if self.state.transition_to_rescheduled()
+                && !self.scheduler.schedule(self.clone())
+            {
+                // [MEMO]
+                // `is_shutdown` を確認した後にランタイムが停止すると、`shutdown` は `NOTIFIED` のタスクを破棄せず、
+                // `schedule` もタスクを受け付けない。誰も "future" を破棄しなくなるので、ここで破棄する。
+                // `shutdown` が先に `RUNNING` へ遷移させていた場合は、そちらが破棄する。
+                if self.state.transition_to_running() {
+                    self.complete();
+                }
+            }
+        }
+    }
+
+    /// "future" を破棄して `COMPLETE` に遷移し、ランタイムにタスクの完了を知らせる
+    ///
+    /// `state` を `RUNNING` に遷移させたスレッドから呼ぶこと。
+    fn complete(&self) {
+        // SAFETY: 呼び出し側が `RUNNING` に遷移させているので、`future` に排他的にアクセスできる
+        // [MEMO]
+        // "future" の破棄中にこのタスク自身が `wake` されても、状態は `NOTIFIED` になるだけでキューには入らない。
+        let future = unsafe { (*self.future.get()).take() };
+
+        // パニックした "future" は、破棄する途中でさらにパニックすることがある。ここでも巻き戻しを止める
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(future)));
+
+        self.state.transition_to_complete();
+        self.scheduler.release(self);
+    }
+
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// タスクをキャンセルする
+    ///
+    /// "future" はここでは破棄せず、タスクをスケジュールして次にワーカーが取り出したときに破棄する。
+    pub(crate) fn abort(self: &Arc<Self>) {
+        if self.state.transition_to_cancelled() {
+            self.scheduler.schedule(self.clone());
+        }
+    }
+
+    /// ランタイムの停止時に、タスクの "future" を破棄する
+    ///
+    /// ワーカーがポーリング中の場合はここでは破棄せず、ポーリングを終えたワーカーが破棄する。
+    pub(crate) fn shutdown(&self) {
+        if self.state.transition_to_shutdown() {
+            self.complete();
+        }
+    }
+
+    // 与えられた "future" に関する新しいタスクを spawn する
+    //
+    // "future" を含むタスクを新しく作り、`scheduler` の実行キューにプッシュする
+    // ワーカーは実行キューからタスクを取得して実行する
+    // [MEMO]
+    // `MiniTokio`の`Handle`を引数に取ることで、`MiniTokio`のインスタンスに対して`Task`を送信することができる
+    pub(crate) fn spawn<F>(future: F, scheduler: &Handle) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (task, join_handle) = Task::new(future, scheduler);
+
+        // 停止したランタイムに spawn されたタスクは、実行せずにすぐ破棄する
+        if scheduler.bind(&task) {
+            scheduler.schedule(task);
+        } else {
+            task.shutdown();
+        }
+
+        join_handle
+    }
+
+    /// `f` を実行するタスクを作り、`scheduler` のブロッキングスレッドに渡す
+    ///
+    /// [MEMO]
+    /// 最初のポーリングで `f` を呼び出して完了する "future" としてタスクを作るため、
+    /// `JoinHandle` や `abort`、パニックの扱いは通常のタスクと同じ仕組みで動く。
+    /// ワーカーのキューには入らず、ブロッキングスレッドが1度だけポーリングする。
+    pub(crate) fn spawn_blocking<F, R>(f: F, scheduler: &Handle) -> JoinHandle<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (task, join_handle) = Task::new(BlockingTask { func: Some(f) }, scheduler);
+
+        if scheduler.bind(&task) {
+            scheduler.spawn_blocking(task);
+        } else {
+            task.shutdown();
+        }
+
+        join_handle
+    }
+
+    /// "future" をラップしたタスクと、その出力を受け取る `JoinHandle` を作る
+    fn new<F>(future: F, scheduler: &Handle) -> (Arc<Task>, JoinHandle<F::Output>)
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (output, join_state) = join::joinable();
+
+        // [MEMO]
+        // `output` は `WithOutput` が保持するため、タスクが完了する前に "future" ごと破棄された場合は
+        // `Output` の `Drop` が呼ばれ、`JoinHandle` 側にエラーが通知される。
+        let future = WithOutput {
+            future,
+            output: Some(output),
+        };
+
+        let task = Arc::new(Task {
+            state: State::new(),
+            future: UnsafeCell::new(Some(Box::pin(future))),
+            scheduler: scheduler.clone(),
+            join: join_state.clone(),
+            id: scheduler.next_task_id(),
+        });
+
+        let join_handle = JoinHandle::new(join_state, task.clone());
+
+        (task, join_handle)
+    }
+}
+
+/// `future` が完了したら、その出力を `output` に書き込む "future"
+///
+/// [MEMO]
+/// 以前は async ブロックで `future.await` の後に書き込んでいたが、async ブロックはパニックの巻き戻しの途中で
+/// キャプチャした `output` を破棄してしまい、パニックより先にキャンセルが書き込まれていた。
+/// 通常の構造体であれば、`poll` からパニックが巻き戻ってもフィールドは破棄されず、
+/// `Task::poll` がパニックを書き込んだ後に、`Task::complete` で破棄される。
+struct WithOutput<F: Future> {
+    future: F,
+    output: Option<join::Output<F::Output>>,
+}
+
+impl<F: Future> Future for WithOutput<F> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // SAFETY: `future` はピン留めしたまま扱い、`self` から移動させない
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        let value = match future.poll(cx) {
+            Poll::Ready(value) => value,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        if let Some(output) = this.output.take() {
+            output.complete(Ok(value));
+        }
+
+        Poll::Ready(())
+    }
+}
+
+/// 最初のポーリングで `func` を呼び出し、その戻り値で完了する "future"
+struct BlockingTask<F> {
+    func: Option<F>,
+}
+
+// `func` をピン留めする必要はない
+impl<F> Unpin for BlockingTask<F> {}
+
+impl<F, R> Future for BlockingTask<F>
+where
+    F: FnOnce() -> R,
+{
+    type Output = R;
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<R> {
+        let func = self
+            .func
+            .take()
+            .expect("blocking task polled after completion");
+
+        Poll::Ready(func())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::Builder;
+    use crate::time::timeout;
+    use crate::MiniTokio;
+    use std::future::{poll_fn, Future};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll, Waker};
+    use std::thread;
+    use std::time::Duration;
+
+    /// ポーリングされた回数を数え、最後にポーリングしたときの "waker" を保存する
+    #[derive(Clone, Default)]
+    struct Probe {
+        polls: Arc<AtomicUsize>,
+        waker: Arc<Mutex<Option<Waker>>>,
+    }
+
+    impl Probe {
+        /// ポーリングされるたびに、何回目かを `f` に渡して結果を決める "future" を返す
+        fn future<F>(&self, mut f: F) -> impl Future<Output = ()> + Send + 'static
+        where
+            F: FnMut(usize, &mut Context<'_>) -> Poll<()> + Send + 'static,
+        {
+            let probe = self.clone();
+
+            poll_fn(move |cx| {
+                let n = probe.polls.fetch_add(1, Ordering::SeqCst) + 1;
+                *probe.waker.lock().unwrap() = Some(cx.waker().clone());
+                f(n, cx)
+            })
+        }
+
+        fn polls(&self) -> usize {
+            self.polls.load(Ordering::SeqCst)
+        }
+
+        fn wake(&self) {
+            self.waker.lock().unwrap().as_ref().unwrap().wake_by_ref();
+        }
+    }
+
+    // 一度だけ `Pending` を返して、他のタスクに順番を譲る
+    async fn yield_now() {
+        let mut yielded = false;
+        poll_fn(|cx| {
+            if yielded {
+                return Poll::Ready(());
+            }
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+        .await
+    }
+
+    // 実行を待っているタスクが全て実行されるまで、順番を譲り続ける
+    // [MEMO]
+    // シミュレーションのランタイムで使う。次に実行するものは乱数で選ばれるが、シードが同じなら結果は毎回同じになる。
+    async fn settle() {
+        for _ in 0..100 {
+            yield_now().await;
+        }
+    }
+
+    #[test]
+    fn not_polled_after_ready() {
+        let mini_tokio = Builder::new().simulation(0).build();
+
+        mini_tokio.block_on(async {
+            let probe = Probe::default();
+            let handle = crate::spawn(probe.future(|_, cx| {
+                cx.waker().wake_by_ref();
+                Poll::Ready(())
+            }));
+            handle.await.unwrap();
+
+            probe.wake();
+            probe.wake();
+            settle().await;
+
+            assert_eq!(probe.polls(), 1);
+        });
+    }
+
+    #[test]
+    fn duplicate_wakes_poll_once() {
+        let mini_tokio = Builder::new().simulation(0).build();
+
+        mini_tokio.block_on(async {
+            let probe = Probe::default();
+            let handle = crate::spawn(probe.future(|n, _| {
+                if n == 3 {
+                    Poll::Ready(())
+                } else {
+                    Poll::Pending
+                }
+            }));
+            settle().await;
+            assert_eq!(probe.polls(), 1);
+
+            probe.wake();
+            probe.wake();
+            probe.wake();
+            settle().await;
+            assert_eq!(probe.polls(), 2);
+
+            probe.wake();
+            handle.await.unwrap();
+            assert_eq!(probe.polls(), 3);
+        });
+
+        // キューに入ったのは、spawn と2回の `wake` の3回だけ
+        let schedule = mini_tokio.recorded_schedule().unwrap();
+        assert_eq!(schedule.iter().filter(|&&id| id == 1).count(), 3);
+    }
+
+    #[test]
+    fn wake_during_poll_repolls_once() {
+        let mini_tokio = Builder::new().simulation(0).build();
+
+        mini_tokio.block_on(async {
+            let probe = Probe::default();
+            let handle = crate::spawn(probe.future(|n, cx| match n {
+                1 => {
+                    cx.waker().wake_by_ref();
+                    cx.waker().wake_by_ref();
+                    cx.waker().wake_by_ref();
+                    Poll::Pending
+                }
+                2 => Poll::Pending,
+                _ => Poll::Ready(()),
+            }));
+            settle().await;
+            assert_eq!(probe.polls(), 2);
+
+            probe.wake();
+            handle.await.unwrap();
+            assert_eq!(probe.polls(), 3);
+        });
+    }
+
+    #[test]
+    fn abort_drops_idle_task_without_polling() {
+        let mini_tokio = Builder::new().simulation(0).build();
+
+        mini_tokio.block_on(async {
+            let probe = Probe::default();
+            let handle = crate::spawn(probe.future(|_, _| Poll::Pending));
+            settle().await;
+            assert_eq!(probe.polls(), 1);
+
+            handle.abort();
+            probe.wake();
+            assert!(handle.await.unwrap_err().is_cancelled());
+            settle().await;
+            assert_eq!(probe.polls(), 1);
+        });
+    }
+
+    // ポーリングのたびに自分自身を `wake` し、`Pending` を返し続ける
+    async fn wake_forever() {
+        poll_fn(|cx| {
+            cx.waker().wake_by_ref();
+            Poll::<()>::Pending
+        })
+        .await
+    }
+
+    #[test]
+    fn shutdown_while_task_wakes_itself() {
+        for _ in 0..50 {
+            let mini_tokio = Builder::new().worker_threads(4).build();
+            let handles: Vec<_> = (0..8).map(|_| mini_tokio.spawn(wake_forever())).collect();
+
+            thread::scope(|scope| {
+                scope.spawn(|| mini_tokio.run());
+                thread::sleep(Duration::from_millis(1));
+                mini_tokio.shutdown();
+            });
+
+            // 停止したランタイムのタスクは全て破棄され、`JoinHandle` はキャンセルを受け取る
+            MiniTokio::new().block_on(async {
+                for handle in handles {
+                    let res = timeout(Duration::from_secs(5), handle)
+                        .await
+                        .expect("the task was never dropped");
+                    assert!(res.unwrap_err().is_cancelled());
+                }
+            });
+        }
+    }
+}
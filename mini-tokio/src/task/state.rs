@@ -0,0 +1,244 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// タスクはワーカーのキューに入っておらず、`wake` を待っている
+const IDLE: usize = 0;
+// タスクはキューに入っていて、ワーカーにポーリングされるのを待っている
+const SCHEDULED: usize = 1;
+// ワーカーが "future" をポーリングしている
+const RUNNING: usize = 2;
+// ポーリング中に `wake` された。ポーリングが終わったら、もう一度だけキューに入れる
+const NOTIFIED: usize = 3;
+// "future" が完了した（または破棄された）。これ以降はポーリングしない
+const COMPLETE: usize = 4;
+
+// 上記のライフサイクルを取り出すためのマスク
+const LIFECYCLE_MASK: usize = 0b111;
+
+// `abort` された。次にワーカーが取り出したときに、ポーリングせずに "future" を破棄する
+// [MEMO]
+// ライフサイクルとは独立したフラグなので、ライフサイクルとは別のビットに保持する。
+const CANCELLED: usize = 0b1000;
+
+/// タスクのライフサイクルを表す状態
+///
+/// - `IDLE` で `wake` されると `SCHEDULED` になり、キューに入る
+/// - ワーカーがキューから取り出すと `RUNNING` になり、"future" をポーリングする
+/// - `Pending` なら `IDLE` に戻る。ポーリング中に `wake` されて `NOTIFIED` になっていれば `SCHEDULED` に戻る
+/// - `Ready` なら "future" を破棄して `COMPLETE` になる
+///
+/// `RUNNING` 状態にしたスレッドだけが "future" にアクセスできる。
+/// `wake` が何度呼ばれても、キューに入るのは `IDLE` から `SCHEDULED` に遷移したときの1回だけである。
+pub(super) struct State {
+    val: AtomicUsize,
+}
+
+impl State {
+    /// spawn した直後のタスクはすぐにキューに入れるので、`SCHEDULED` から始める
+    pub(super) fn new() -> State {
+        State {
+            val: AtomicUsize::new(SCHEDULED),
+        }
+    }
+
+    /// `wake` されたときの遷移。呼び出し側がタスクをキューに入れる必要がある場合は `true` を返す
+    pub(super) fn transition_to_scheduled(&self) -> bool {
+        self.transition(|curr| match curr {
+            IDLE => Some(SCHEDULED),
+            // ポーリング中のタスクは、ポーリングが終わったワーカーがキューに入れ直す
+            RUNNING => Some(NOTIFIED),
+            // すでにキューに入っているか、再ポーリングが予約されているか、完了している
+            _ => None,
+        })
+        .is_ok_and(|prev| prev == IDLE)
+    }
+
+    /// キューから取り出したタスクをポーリングする前の遷移。"future" にアクセスしてよい場合は `true` を返す
+    pub(super) fn transition_to_running(&self) -> bool {
+        self.transition(|curr| (curr == SCHEDULED).then_some(RUNNING))
+            .is_ok()
+    }
+
+    /// `Pending` を返した後の遷移
+    ///
+    /// ポーリング中に `wake` されていた場合は `false` を返す。このとき状態は `NOTIFIED` のままなので、
+    /// 呼び出し側は `transition_to_rescheduled` でタスクをキューに入れ直す。
+    pub(super) fn transition_to_idle(&self) -> bool {
+        self.transition(|curr| (curr == RUNNING).then_some(IDLE))
+            .is_ok()
+    }
+
+    /// `NOTIFIED` のタスクをキューに入れ直すときの遷移。呼び出し側がタスクをキューに入れる必要がある場合は `true` を返す
+    ///
+    /// [MEMO]
+    /// 他の遷移と同じく、`NOTIFIED` のときだけ遷移する。
+    /// 状態を確認せずに上書きすると、他のスレッドが行った遷移を打ち消してしまう。
+    pub(super) fn transition_to_rescheduled(&self) -> bool {
+        self.transition(|curr| (curr == NOTIFIED).then_some(SCHEDULED))
+            .is_ok()
+    }
+
+    /// "future" を破棄した後の遷移。`RUNNING` か `NOTIFIED` の状態から呼ぶこと
+    pub(super) fn transition_to_complete(&self) {
+        let _ = self.transition(|_| Some(COMPLETE));
+    }
+
+    /// `abort` されたときの遷移。呼び出し側がタスクをキューに入れる必要がある場合は `true` を返す
+    ///
+    /// "future" の破棄はワーカーに任せるため、`wake` と同じようにタスクをスケジュールする。
+    pub(super) fn transition_to_cancelled(&self) -> bool {
+        let mut curr = self.val.load(Ordering::Acquire);
+
+        loop {
+            if curr & CANCELLED != 0 {
+                return false;
+            }
+
+            let next = match curr & LIFECYCLE_MASK {
+                IDLE => SCHEDULED,
+                RUNNING => NOTIFIED,
+                COMPLETE => return false,
+                lifecycle => lifecycle,
+            };
+
+            match self.val.compare_exchange(
+                curr,
+                next | CANCELLED,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return curr & LIFECYCLE_MASK == IDLE,
+                Err(actual) => curr = actual,
+            }
+        }
+    }
+
+    /// ランタイムの停止時の遷移
+    ///
+    /// ポーリングされていないタスクは `RUNNING` にして `true` を返し、呼び出し側が "future" を破棄する。
+    /// ポーリング中のタスクは `NOTIFIED` にしておき、ポーリングを終えたワーカーに破棄を任せる。
+    pub(super) fn transition_to_shutdown(&self) -> bool {
+        self.transition(|curr| match curr {
+            IDLE | SCHEDULED => Some(RUNNING),
+            RUNNING => Some(NOTIFIED),
+            _ => None,
+        })
+        .is_ok_and(|prev| prev != RUNNING)
+    }
+
+    pub(super) fn is_cancelled(&self) -> bool {
+        self.val.load(Ordering::Acquire) & CANCELLED != 0
+    }
+
+    /// `f` にライフサイクルを渡して遷移先を決める。`f` が `None` を返した場合は遷移しない
+    ///
+    /// `CANCELLED` フラグは保持したまま遷移する。遷移した場合は遷移前のライフサイクルを返す。
+    fn transition(&self, mut f: impl FnMut(usize) -> Option<usize>) -> Result<usize, usize> {
+        self.val
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |curr| {
+                f(curr & LIFECYCLE_MASK).map(|next| (curr & !LIFECYCLE_MASK) | next)
+            })
+            .map(|prev| prev & LIFECYCLE_MASK)
+            .map_err(|prev| prev & LIFECYCLE_MASK)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lifecycle(state: &State) -> usize {
+        state.val.load(Ordering::Acquire) & LIFECYCLE_MASK
+    }
+
+    /// `RUNNING` まで進めた状態を作る
+    fn running() -> State {
+        let state = State::new();
+        assert!(state.transition_to_running());
+        state
+    }
+
+    #[test]
+    fn wake_while_idle_schedules_once() {
+        let state = running();
+        assert!(state.transition_to_idle());
+
+        assert!(state.transition_to_scheduled());
+        assert!(!state.transition_to_scheduled());
+        assert_eq!(lifecycle(&state), SCHEDULED);
+    }
+
+    #[test]
+    fn wake_while_running_reschedules_once() {
+        let state = running();
+
+        assert!(!state.transition_to_scheduled());
+        assert!(!state.transition_to_scheduled());
+        assert_eq!(lifecycle(&state), NOTIFIED);
+
+        assert!(!state.transition_to_idle());
+        assert!(state.transition_to_rescheduled());
+        assert!(!state.transition_to_rescheduled());
+        assert_eq!(lifecycle(&state), SCHEDULED);
+    }
+
+    #[test]
+    fn complete_is_final() {
+        let state = running();
+        state.transition_to_complete();
+
+        assert!(!state.transition_to_scheduled());
+        assert!(!state.transition_to_running());
+        assert!(!state.transition_to_rescheduled());
+        assert!(!state.transition_to_cancelled());
+        assert!(!state.transition_to_shutdown());
+        assert_eq!(lifecycle(&state), COMPLETE);
+    }
+
+    #[test]
+    fn cancel_keeps_the_flag_across_transitions() {
+        // `IDLE` のタスクは、呼び出し側がキューに入れる
+        let state = running();
+        assert!(state.transition_to_idle());
+        assert!(state.transition_to_cancelled());
+        assert!(state.is_cancelled());
+        assert_eq!(lifecycle(&state), SCHEDULED);
+
+        // 2回目の `abort` では何もしない
+        assert!(!state.transition_to_cancelled());
+
+        assert!(state.transition_to_running());
+        assert!(state.is_cancelled());
+    }
+
+    #[test]
+    fn cancel_while_running_reschedules() {
+        let state = running();
+
+        assert!(!state.transition_to_cancelled());
+        assert!(state.is_cancelled());
+        assert_eq!(lifecycle(&state), NOTIFIED);
+    }
+
+    #[test]
+    fn shutdown_takes_idle_and_scheduled_tasks() {
+        let state = State::new();
+        assert!(state.transition_to_shutdown());
+        assert_eq!(lifecycle(&state), RUNNING);
+
+        let state = running();
+        assert!(state.transition_to_idle());
+        assert!(state.transition_to_shutdown());
+        assert_eq!(lifecycle(&state), RUNNING);
+    }
+
+    #[test]
+    fn shutdown_leaves_running_task_to_the_worker() {
+        let state = running();
+        assert!(!state.transition_to_shutdown());
+        assert_eq!(lifecycle(&state), NOTIFIED);
+
+        // `NOTIFIED` のタスクに対しては、2回目の `shutdown` も何もしない
+        assert!(!state.transition_to_shutdown());
+        assert_eq!(lifecycle(&state), NOTIFIED);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::task::Task;
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::task::{RawWaker, RawWakerVTable, Waker};
+
+// `Task` の "waker" が呼び出す関数の一覧
+// [MEMO]
+// "waker" のデータには `Arc::into_raw` で得た `Task` へのポインタをそのまま使う。
+// `Arc` の参照カウントがタスクのヘッダの役割を果たし、"waker" の複製と破棄は参照カウントの増減だけで済む。
+static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop_waker);
+
+/// `task` の参照カウントを増やさずに作った "waker"
+///
+/// 破棄しても参照カウントは減らない。`clone` した "waker" は、通常どおり参照カウントを持つ。
+pub(super) struct WakerRef<'a> {
+    waker: ManuallyDrop<Waker>,
+    _task: PhantomData<&'a Arc<Task>>,
+}
+
+impl Deref for WakerRef<'_> {
+    type Target = Waker;
+
+    fn deref(&self) -> &Waker {
+        &self.waker
+    }
+}
+
+/// ポーリングの間だけ使う "waker" を、`task` を借用して作る
+///
+/// [MEMO]
+/// 以前はポーリングのたびに `Arc<Task>` を複製して "waker" を作っていた。
+/// "future" が "waker" を保存するときは `clone` されるので、ここで参照カウントを増やす必要はない。
+pub(super) fn waker_ref(task: &Arc<Task>) -> WakerRef<'_> {
+    let ptr = Arc::as_ptr(task).cast::<()>();
+
+    // SAFETY: `WakerRef` は `task` を借用しているので、使われている間 `Task` は破棄されない。
+    // `ManuallyDrop` に入れているので、借りている参照カウントを `drop_waker` で減らすこともない。
+    let waker = unsafe { Waker::from_raw(RawWaker::new(ptr, &VTABLE)) };
+
+    WakerRef {
+        waker: ManuallyDrop::new(waker),
+        _task: PhantomData,
+    }
+}
+
+// 以下の関数の `ptr` は、いずれも `Task` を指すポインタである。
+// `wake` と `drop_waker` に渡されるものは、`clone` で増やした参照カウントを1つ持っている
+
+unsafe fn clone(ptr: *const ()) -> RawWaker {
+    // SAFETY: `ptr` が指す `Task` は生きているので、参照カウントを増やしてよい
+    unsafe { Arc::increment_strong_count(ptr.cast::<Task>()) };
+    RawWaker::new(ptr, &VTABLE)
+}
+
+unsafe fn wake(ptr: *const ()) {
+    // SAFETY: `wake` は "waker" を消費するので、"waker" が持っていた参照カウントを引き継ぐ
+    let task = unsafe { Arc::from_raw(ptr.cast::<Task>()) };
+    task.schedule();
+}
+
+unsafe fn wake_by_ref(ptr: *const ()) {
+    // SAFETY: 参照カウントは "waker" が持ち続けるので、`ManuallyDrop` に入れて減らさないようにする
+    // [MEMO]
+    // `Arc` を複製しないので、キューに入れる必要がないタスク（すでにキューに入っている、完了しているなど）では
+    // 参照カウントの操作が一切発生しない。
+    let task = ManuallyDrop::new(unsafe { Arc::from_raw(ptr.cast::<Task>()) });
+    task.schedule();
+}
+
+unsafe fn drop_waker(ptr: *const ()) {
+    // SAFETY: "waker" が持っていた参照カウントを1つ減らす
+    unsafe { drop(Arc::from_raw(ptr.cast::<Task>())) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::waker_ref;
+    use crate::runtime::Handle;
+    use crate::task::Task;
+    use crate::MiniTokio;
+    use std::sync::Arc;
+
+    #[test]
+    fn clone_and_drop_balance_the_ref_count() {
+        MiniTokio::new().block_on(async {
+            // spawn せずに作ったタスクは `SCHEDULED` のままなので、`wake` してもキューには入らない
+            let (task, _join) = Task::new(async {}, &Handle::current());
+            let base = Arc::strong_count(&task);
+
+            {
+                let waker = waker_ref(&task);
+                assert_eq!(Arc::strong_count(&task), base);
+
+                let first = (*waker).clone();
+                assert_eq!(Arc::strong_count(&task), base + 1);
+                assert!(first.will_wake(&waker));
+
+                let second = first.clone();
+                assert_eq!(Arc::strong_count(&task), base + 2);
+
+                drop(first);
+                assert_eq!(Arc::strong_count(&task), base + 1);
+
+                second.wake_by_ref();
+                assert_eq!(Arc::strong_count(&task), base + 1);
+
+                // `wake` は "waker" が持っていた参照カウントを消費する
+                second.wake();
+                assert_eq!(Arc::strong_count(&task), base);
+            }
+
+            assert_eq!(Arc::strong_count(&task), base);
+        });
+    }
+}
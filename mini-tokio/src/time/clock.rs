@@ -0,0 +1,293 @@
+use crate::runtime::Handle;
+use std::future::poll_fn;
+use std::sync::Mutex;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+/// ランタイムの時刻
+///
+/// タイマーや `Sleep` は `Instant::now()` を直接読まず、この時刻を使う。
+/// 一時停止している間は時刻が進まず、`advance` で進めたときだけ進む。
+///
+/// [MEMO]
+/// 一時停止を解除した後も、それまでに進めた分のずれは残る。
+/// ランタイムの時刻は、解除した時点の時刻から実際の経過時間だけ進む。
+pub(crate) struct Clock {
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    // 最後に一時停止、または一時停止を解除した時点のランタイムの時刻
+    base: Instant,
+    // 一時停止を解除した実際の時刻。一時停止している間は `None`
+    unfrozen: Option<Instant>,
+}
+
+impl Clock {
+    pub(crate) fn new(start_paused: bool) -> Clock {
+        let now = Instant::now();
+
+        Clock {
+            inner: Mutex::new(Inner {
+                base: now,
+                unfrozen: if start_paused { None } else { Some(now) },
+            }),
+        }
+    }
+
+    /// ランタイムの現在時刻を返す
+    pub(crate) fn now(&self) -> Instant {
+        self.inner.lock().unwrap().now()
+    }
+
+    /// 一時停止しているかどうか
+    pub(crate) fn is_paused(&self) -> bool {
+        self.inner.lock().unwrap().unfrozen.is_none()
+    }
+
+    /// 時刻を一時停止する
+    ///
+    /// # Panics
+    ///
+    /// すでに一時停止している場合はパニックする。
+    pub(crate) fn pause(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        assert!(inner.unfrozen.is_some(), "time is already frozen");
+
+        inner.base = inner.now();
+        inner.unfrozen = None;
+    }
+
+    /// 一時停止を解除する
+    ///
+    /// # Panics
+    ///
+    /// 一時停止していない場合はパニックする。
+    pub(crate) fn resume(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        assert!(inner.unfrozen.is_none(), "time is not frozen");
+
+        inner.unfrozen = Some(Instant::now());
+    }
+
+    /// 一時停止している時刻を `duration` だけ進める
+    ///
+    /// # Panics
+    ///
+    /// 一時停止していない場合はパニックする。
+    pub(crate) fn advance(&self, duration: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        assert!(inner.unfrozen.is_none(), "time is not frozen");
+
+        inner.base += duration;
+    }
+
+    /// 一時停止している時刻を `deadline` まで進める
+    ///
+    /// すでに `deadline` を過ぎている場合や、一時停止していない場合は何もしない。
+    pub(crate) fn advance_to(&self, deadline: Instant) {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.unfrozen.is_none() && inner.base < deadline {
+            inner.base = deadline;
+        }
+    }
+
+    /// ランタイムの時刻での期限を、ワーカーが眠るときに使う実際の時刻での期限に変換する
+    ///
+    /// 一時停止している間は時刻が進まないので、期限が来ることはなく `None` を返す。
+    pub(crate) fn park_deadline(&self, deadline: Option<Instant>) -> Option<Instant> {
+        let inner = self.inner.lock().unwrap();
+        let unfrozen = inner.unfrozen?;
+        let deadline = deadline?;
+
+        // 実際の時刻とランタイムの時刻のずれを戻す
+        let now = Instant::now();
+        Some(now + deadline.saturating_duration_since(inner.base + (now - unfrozen)))
+    }
+}
+
+impl Inner {
+    fn now(&self) -> Instant {
+        match self.unfrozen {
+            Some(unfrozen) => self.base + unfrozen.elapsed(),
+            None => self.base,
+        }
+    }
+}
+
+/// ランタイムの現在時刻を返す
+///
+/// ランタイムの外から呼び出された場合は `Instant::now()` を返す。
+/// 時刻を一時停止しているときは、`Instant::now()` ではなくこの関数で現在時刻を取得すること。
+pub fn now() -> Instant {
+    match Handle::try_current() {
+        Some(handle) => handle.clock().now(),
+        None => Instant::now(),
+    }
+}
+
+/// ランタイムの時刻を一時停止する
+///
+/// 一時停止している間は、`advance` を呼んだときだけ時刻が進む。
+/// また、全てのタスクがタイマーなどを待って止まっている場合は、次のタイマーの期限まで自動で時刻が進む。
+/// そのため、時間に関する処理を実際に待たずにテストできる。
+///
+/// ```no_run
+/// use mini_tokio::time::{self, sleep};
+/// use mini_tokio::MiniTokio;
+/// use std::time::{Duration, Instant};
+///
+/// let mini_tokio = MiniTokio::new();
+///
+/// mini_tokio.block_on(async {
+///     time::pause();
+///
+///     let start = Instant::now();
+///     let now = time::now();
+///
+///     // 実際には待たずに、すぐに完了する
+///     sleep(Duration::from_secs(60)).await;
+///
+///     assert!(time::now() - now >= Duration::from_secs(60));
+///     assert!(start.elapsed() < Duration::from_secs(1));
+/// });
+/// ```
+///
+/// # Panics
+///
+/// ランタイムの外から呼び出された場合や、すでに一時停止している場合はパニックする。
+pub fn pause() {
+    Handle::current().clock().pause();
+}
+
+/// ランタイムの時刻の一時停止を解除する
+///
+/// # Panics
+///
+/// ランタイムの外から呼び出された場合や、一時停止していない場合はパニックする。
+pub fn resume() {
+    let handle = Handle::current();
+    handle.clock().resume();
+
+    // 一時停止の間、期限を決めずに眠っていたワーカーを起こして、期限を計算し直させる
+    handle.unpark_all();
+}
+
+/// 一時停止しているランタイムの時刻を `duration` だけ進める
+///
+/// 期限を過ぎたタイマーに通知してから、他のタスクが実行されるよう一度だけ処理を譲る。
+///
+/// # Panics
+///
+/// ランタイムの外から呼び出された場合や、一時停止していない場合はパニックする。
+pub async fn advance(duration: Duration) {
+    let handle = Handle::current();
+    handle.clock().advance(duration);
+    handle.process_timers();
+
+    // [MEMO]
+    // 一度 `Pending` を返して、起こされたタスクが先に実行されるようにする。
+    let mut yielded = false;
+    poll_fn(|cx| {
+        if yielded {
+            return Poll::Ready(());
+        }
+
+        yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::Builder;
+    use crate::sync::mpsc;
+    use crate::time::{self, interval, sleep};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn paused_sleep_completes_immediately() {
+        for workers in [1, 4] {
+            let mini_tokio = Builder::new()
+                .worker_threads(workers)
+                .start_paused(true)
+                .build();
+            let real = Instant::now();
+
+            mini_tokio.block_on(async {
+                let start = time::now();
+                sleep(Duration::from_secs(3600)).await;
+                assert_eq!(time::now() - start, Duration::from_secs(3600));
+            });
+
+            assert!(real.elapsed() < Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn advance_fires_due_timers() {
+        for workers in [1, 4] {
+            let mini_tokio = Builder::new()
+                .worker_threads(workers)
+                .start_paused(true)
+                .build();
+
+            mini_tokio.block_on(async {
+                let start = time::now();
+                let (tx, mut rx) = mpsc::channel(8);
+
+                for millis in [10, 20, 30] {
+                    let tx = tx.clone();
+                    // 期限は `sleep` を呼んだ時点で決まるので、spawn したタスクがポーリングされる前に作っておく
+                    let delay = sleep(Duration::from_millis(millis));
+                    crate::spawn(async move {
+                        delay.await;
+                        tx.send(millis).await.unwrap();
+                    });
+                }
+
+                time::advance(Duration::from_millis(25)).await;
+
+                // 期限を過ぎた 10 ms と 20 ms のタイマーだけが通知される
+                // [MEMO]
+                // 受け取るまでの間はこのスレッドが参加者として数えられているので、自動で時刻は進まない。
+                let mut fired = vec![rx.recv().await.unwrap(), rx.recv().await.unwrap()];
+                fired.sort();
+                assert_eq!(fired, [10, 20]);
+                assert_eq!(time::now() - start, Duration::from_millis(25));
+                assert!(rx.try_recv().is_err());
+
+                time::advance(Duration::from_millis(5)).await;
+                assert_eq!(rx.recv().await, Some(30));
+                assert_eq!(time::now() - start, Duration::from_millis(30));
+            });
+        }
+    }
+
+    #[test]
+    fn interval_ticks_with_auto_advance() {
+        for workers in [1, 4] {
+            let mini_tokio = Builder::new()
+                .worker_threads(workers)
+                .start_paused(true)
+                .build();
+            let real = Instant::now();
+
+            mini_tokio.block_on(async {
+                let start = time::now();
+                let mut interval = interval(Duration::from_secs(10));
+
+                for i in 0..5 {
+                    let tick = interval.tick().await;
+                    assert_eq!(tick - start, Duration::from_secs(10 * i));
+                    assert_eq!(time::now(), tick);
+                }
+            });
+
+            assert!(real.elapsed() < Duration::from_secs(1));
+        }
+    }
+}
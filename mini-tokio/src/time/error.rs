@@ -0,0 +1,22 @@
+//! 時間に関する処理が返すエラー
+
+use std::error::Error;
+use std::fmt;
+
+/// `timeout` の期限までに "future" が完了しなかったことを表すエラー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed(());
+
+impl Elapsed {
+    pub(super) fn new() -> Elapsed {
+        Elapsed(())
+    }
+}
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+impl Error for Elapsed {}
@@ -0,0 +1,147 @@
+use crate::time::clock;
+use crate::time::{sleep_until, Sleep};
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// `period` ごとに完了する `Interval` を作る。最初の `tick` はすぐに完了する
+///
+/// # Panics
+///
+/// `period` が 0 の場合はパニックする。
+///
+/// ```no_run
+/// use mini_tokio::time::interval;
+/// use mini_tokio::MiniTokio;
+/// use std::time::Duration;
+///
+/// let mini_tokio = MiniTokio::new();
+///
+/// mini_tokio.block_on(async {
+///     let mut interval = interval(Duration::from_millis(10));
+///
+///     interval.tick().await; // すぐに完了する
+///     interval.tick().await; // 10 ms 後に完了する
+///     interval.tick().await; // 20 ms 後に完了する
+/// });
+/// ```
+pub fn interval(period: Duration) -> Interval {
+    interval_at(clock::now(), period)
+}
+
+/// `start` から `period` ごとに完了する `Interval` を作る。最初の `tick` は `start` に完了する
+///
+/// # Panics
+///
+/// `period` が 0 の場合はパニックする。
+pub fn interval_at(start: Instant, period: Duration) -> Interval {
+    assert!(period > Duration::ZERO, "`period` must be non-zero");
+
+    Interval {
+        delay: sleep_until(start),
+        period,
+        missed_tick_behavior: MissedTickBehavior::default(),
+    }
+}
+
+/// `tick` の呼び出しが遅れて、予定の時刻を逃したときの振る舞い
+///
+/// 例えば周期が 10 ms で、0 ms の `tick` の後に次の `tick` を呼んだのが 35 ms だった場合、
+/// 10 ms、20 ms、30 ms の3回を逃している。
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MissedTickBehavior {
+    /// 逃した回数だけ、すぐに続けて完了する。その後は元の予定通りに完了する
+    ///
+    /// 上の例では、35 ms に3回続けて完了し、次は 40 ms に完了する。
+    #[default]
+    Burst,
+    /// 逃した分は1回だけ完了し、その時刻から周期を数え直す
+    ///
+    /// 上の例では、35 ms に1回完了し、次は 45 ms に完了する。
+    Delay,
+    /// 逃した分は1回だけ完了し、その後は元の予定のうち、まだ来ていない時刻に完了する
+    ///
+    /// 上の例では、35 ms に1回完了し、次は 40 ms に完了する。
+    Skip,
+}
+
+impl MissedTickBehavior {
+    /// 予定の時刻 `timeout` の `tick` が `now` に完了したときの、次の予定の時刻を返す
+    fn next_timeout(self, timeout: Instant, now: Instant, period: Duration) -> Instant {
+        match self {
+            MissedTickBehavior::Burst => timeout + period,
+            MissedTickBehavior::Delay => now + period,
+            MissedTickBehavior::Skip => {
+                // `now` より後で、`timeout` から周期の整数倍だけ進んだ最初の時刻
+                let elapsed = (now - timeout).as_nanos();
+                let period_nanos = period.as_nanos();
+                let remaining = period_nanos - elapsed % period_nanos;
+
+                now + Duration::from_nanos(remaining as u64)
+            }
+        }
+    }
+}
+
+/// `interval` と `interval_at` が返す、一定の周期で完了するタイマー
+#[derive(Debug)]
+pub struct Interval {
+    // 次の `tick` の予定の時刻に完了する
+    delay: Sleep,
+    period: Duration,
+    missed_tick_behavior: MissedTickBehavior,
+}
+
+impl Interval {
+    /// 次の予定の時刻まで待ち、その時刻を返す
+    ///
+    /// [MEMO]
+    /// 返すのは予定の時刻で、実際に完了した時刻ではない。
+    pub async fn tick(&mut self) -> Instant {
+        poll_fn(|cx| self.poll_tick(cx)).await
+    }
+
+    /// 次の予定の時刻になっていれば、その時刻を返す。なっていなければ "waker" を登録する
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+        if Pin::new(&mut self.delay).poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        let timeout = self.delay.deadline();
+        let now = clock::now();
+
+        // [MEMO]
+        // 次の予定の時刻もすでに過ぎている場合に、予定の時刻を逃したとみなす。
+        let next = if now >= timeout + self.period {
+            self.missed_tick_behavior
+                .next_timeout(timeout, now, self.period)
+        } else {
+            timeout + self.period
+        };
+
+        self.delay.reset(next);
+
+        Poll::Ready(timeout)
+    }
+
+    /// 今から1周期後に次の `tick` が完了するように、予定を組み直す
+    pub fn reset(&mut self) {
+        self.delay.reset(clock::now() + self.period);
+    }
+
+    /// 周期を返す
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// 予定の時刻を逃したときの振る舞いを返す
+    pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+        self.missed_tick_behavior
+    }
+
+    /// 予定の時刻を逃したときの振る舞いを設定する
+    pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+        self.missed_tick_behavior = behavior;
+    }
+}
@@ -0,0 +1,51 @@
+//! 時間を待つための "future"
+//!
+//! どの "future" もランタイムのタイマーに登録して待つので、ランタイムのコンテキストの中でポーリングすること。
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+mod clock;
+pub(crate) use clock::Clock;
+pub use clock::{advance, now, pause, resume};
+
+pub mod error;
+
+mod interval;
+pub use interval::{interval, interval_at, Interval, MissedTickBehavior};
+
+mod sleep;
+pub use sleep::{sleep, sleep_until, Sleep};
+
+mod timeout;
+pub use timeout::{timeout, timeout_at, Timeout};
+
+mod timer;
+pub(crate) use timer::{Timer, TimerKey};
+
+/// 指定した時刻になるまで `Pending` を返し続ける "future"
+///
+/// [MEMO]
+/// 期限の変更などができる `Sleep` が追加されたので、中身は `Sleep` に任せている。
+pub struct Delay {
+    sleep: Sleep,
+}
+
+impl Delay {
+    /// `when` に完了する `Delay` を生成する
+    pub fn new(when: Instant) -> Delay {
+        Delay {
+            sleep: sleep_until(when),
+        }
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        Pin::new(&mut self.sleep).poll(cx)
+    }
+}
@@ -0,0 +1,144 @@
+use crate::runtime::Handle;
+use crate::sync::{Notify, OwnedNotified};
+use crate::time::{clock, TimerKey};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// `duration` が経過するまで待つ
+///
+/// 待ち始める時刻は `sleep` を呼んだ時点で決まる。`.await` した時点ではない。
+///
+/// ```no_run
+/// use mini_tokio::time::sleep;
+/// use mini_tokio::MiniTokio;
+/// use std::time::Duration;
+///
+/// let mini_tokio = MiniTokio::new();
+///
+/// mini_tokio.block_on(async {
+///     sleep(Duration::from_millis(100)).await;
+///     println!("100 ms have elapsed");
+/// });
+/// ```
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(clock::now() + duration)
+}
+
+/// `deadline` になるまで待つ
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline,
+        registration: None,
+    }
+}
+
+/// `sleep` と `sleep_until` が返す "future"
+///
+/// 期限になるまで `Pending` を返し続ける。
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Sleep {
+    deadline: Instant,
+    // 最初のポーリングで登録したタイマー
+    registration: Option<Registration>,
+}
+
+/// ランタイムのタイマーへの登録
+///
+/// 破棄されると、期限を迎えていないタイマーを取り除く。
+/// [MEMO]
+/// 完了する前に `Sleep` が破棄されても（`timeout` や `select!` で他が先に完了した場合など）、タイマーは期限まで残っていた。
+/// 期限になると誰も待っていない `Notify` に通知し、時刻が一時停止されている場合は、その期限まで時刻を進めてしまっていた。
+/// 破棄された時点で取り除くことで、使われなくなった `Sleep` はタイマーに何も残さない。
+struct Registration {
+    handle: Handle,
+    key: TimerKey,
+    // タイマーからの通知を待つ "future"
+    // [MEMO]
+    // 以前の `Delay` は `Arc<Mutex<Waker>>` をタイマーと共有し、ポーリングのたびに "waker" を自分で更新していた。
+    // `Notify` が "waker" の保存と更新を行うので、ここでは通知を待つだけでよい。
+    // [MEMO]
+    // `OwnedNotified` はピン留めが必要なので、`Sleep` 自身を `Unpin` に保つために `Box` に入れている。
+    notified: Pin<Box<OwnedNotified>>,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.handle.deregister_timer(self.key);
+    }
+}
+
+impl Sleep {
+    /// 期限を返す
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// 期限を過ぎているかどうか
+    pub fn is_elapsed(&self) -> bool {
+        clock::now() >= self.deadline
+    }
+
+    /// 期限を `deadline` に変更する
+    ///
+    /// すでに完了した `Sleep` も、新しい期限まで待つようになる。
+    ///
+    /// [MEMO]
+    /// 変更前の期限で登録したタイマーは取り除かれる。
+    /// 新しい期限のタイマーは次のポーリングで登録されるので、変更した後はもう一度ポーリングすること。
+    pub fn reset(&mut self, deadline: Instant) {
+        self.deadline = deadline;
+        self.registration = None;
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // 期限を過ぎているなら、"future" は完了しているので、`Poll::Ready` を返す
+        if clock::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+
+        // これが "future" の初めての呼び出しであるならば、ランタイムのタイマーに登録する
+        // [MEMO]
+        // 以前は `Delay` ごとにタイマースレッドを spawn して sleep していたが、
+        // それでは `Delay` の数だけ OS スレッドが必要になる。
+        // 登録したタイマーは `MiniTokio::run` のループが期限を確認し、期限を過ぎたら `Notify` に通知する。
+        let deadline = self.deadline;
+        let registration = self.registration.get_or_insert_with(|| {
+            let handle = Handle::current();
+            let notify = Arc::new(Notify::new());
+            let key = handle.register_timer(deadline, notify.clone());
+
+            Registration {
+                handle,
+                key,
+                notified: Box::pin(notify.notified_owned()),
+            }
+        });
+
+        // タイマーから通知されるまで待つ
+        // [MEMO]
+        // `Pending` が返されるときには、"future" が再度ポーリングされるべき状況になったときに
+        // "waker" へと確実に合図を送らなければならない。
+        // `Notified` は、通知を受け取ったときに最後にポーリングしたタスクの "waker" を呼び起こすことを保証している。
+        // `Sleep` が複数回の `poll` 呼び出しで異なるタスクへとムーブした場合も、`Notified` が "waker" を更新する。
+        //
+        // [MEMO]
+        // タイマーは期限を過ぎてから通知するので、通知を受け取った時点で指定時間は経過している。
+        registration.notified.as_mut().poll(cx)
+    }
+}
+
+impl fmt::Debug for Sleep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sleep")
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
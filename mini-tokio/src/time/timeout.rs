@@ -0,0 +1,94 @@
+use crate::time::clock;
+use crate::time::error::Elapsed;
+use crate::time::{sleep_until, Sleep};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// `future` の完了を `duration` まで待つ
+///
+/// 期限までに完了すれば出力を `Ok` で返し、完了しなければ `future` を破棄して `Elapsed` を返す。
+///
+/// ```no_run
+/// use mini_tokio::sync::oneshot;
+/// use mini_tokio::time::timeout;
+/// use mini_tokio::MiniTokio;
+/// use std::time::Duration;
+///
+/// let mini_tokio = MiniTokio::new();
+///
+/// mini_tokio.block_on(async {
+///     let (_tx, rx) = oneshot::channel::<u32>();
+///
+///     // 送信されないので、10 ms 後に `Elapsed` が返る
+///     let res = timeout(Duration::from_millis(10), rx).await;
+///     assert!(res.is_err());
+/// });
+/// ```
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    timeout_at(clock::now() + duration, future)
+}
+
+/// `future` の完了を `deadline` まで待つ
+pub fn timeout_at<F: Future>(deadline: Instant, future: F) -> Timeout<F> {
+    Timeout {
+        value: future,
+        delay: sleep_until(deadline),
+    }
+}
+
+/// `timeout` と `timeout_at` が返す "future"
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Timeout<F> {
+    value: F,
+    delay: Sleep,
+}
+
+impl<F> Timeout<F> {
+    /// 包んでいる "future" への参照を返す
+    pub fn get_ref(&self) -> &F {
+        &self.value
+    }
+
+    /// 包んでいる "future" への可変参照を返す
+    pub fn get_mut(&mut self) -> &mut F {
+        &mut self.value
+    }
+
+    /// `Timeout` を消費して、包んでいる "future" を取り出す
+    pub fn into_inner(self) -> F {
+        self.value
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `value` はピン留めしたまま扱い、`self` から移動させない
+        let this = unsafe { self.get_unchecked_mut() };
+        let value = unsafe { Pin::new_unchecked(&mut this.value) };
+
+        // [MEMO]
+        // 期限と同時に完了した場合は出力を優先するため、先に "future" をポーリングする。
+        if let Poll::Ready(output) = value.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+
+        match Pin::new(&mut this.delay).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(Elapsed::new())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<F: fmt::Debug> fmt::Debug for Timeout<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Timeout")
+            .field("value", &self.value)
+            .field("delay", &self.delay)
+            .finish()
+    }
+}
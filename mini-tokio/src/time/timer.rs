@@ -0,0 +1,75 @@
+use crate::sync::Notify;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// ランタイムが所有するタイマー
+///
+/// 登録された `Notify` を期限の早い順に保持し、`MiniTokio::run` のループから `process` が呼ばれるたびに
+/// 期限を過ぎたものを取り出して通知する。
+/// `Delay` ごとにスレッドを spawn する代わりに、この1つのキューで全てのタイマーを管理する。
+pub(crate) struct Timer {
+    // [MEMO]
+    // `BTreeMap` はキーの順に要素を保持するので、先頭の要素が最も期限の早いタイマーになる。
+    // 同じ期限のタイマーを区別するため、キーには登録順の連番も含めている。
+    entries: BTreeMap<TimerKey, Arc<Notify>>,
+    next_id: u64,
+}
+
+/// 登録したタイマーを取り除くためのキー
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct TimerKey(Instant, u64);
+
+impl Timer {
+    pub(crate) fn new() -> Timer {
+        Timer {
+            entries: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    /// `when` に通知される `Notify` を登録し、取り除くためのキーを返す
+    pub(crate) fn register(&mut self, when: Instant, notify: Arc<Notify>) -> TimerKey {
+        let key = TimerKey(when, self.next_id);
+        self.next_id += 1;
+
+        self.entries.insert(key, notify);
+        key
+    }
+
+    /// まだ期限を迎えていないタイマーを取り除き、その `Notify` を返す
+    ///
+    /// すでに期限を迎えて取り除かれている場合は `None` を返す。
+    pub(crate) fn deregister(&mut self, key: TimerKey) -> Option<Arc<Notify>> {
+        self.entries.remove(&key)
+    }
+
+    /// 最も早いタイマーの期限を返す
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.entries.keys().next().map(|key| key.0)
+    }
+
+    /// `now` までに期限を迎えたタイマーを取り除き、その `Notify` を返す
+    ///
+    /// 通知はタイマーのロックを解放してから行うこと。
+    pub(crate) fn process(&mut self, now: Instant) -> Vec<Arc<Notify>> {
+        let mut expired = Vec::new();
+
+        while let Some(entry) = self.entries.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+
+            expired.push(entry.remove());
+        }
+
+        expired
+    }
+
+    /// 全てのタイマーを取り除き、その `Notify` を返す
+    ///
+    /// ランタイムの停止時に呼ばれる。`Notify` の破棄はタイマーのロックを解放してから行うこと。
+    pub(crate) fn clear(&mut self) -> Vec<Arc<Notify>> {
+        std::mem::take(&mut self.entries).into_values().collect()
+    }
+}
@@ -0,0 +1,117 @@
+use crate::rt::TcpStream;
+use bytes::{Buf, BytesMut};
+use mini_redis::frame::{self, Frame};
+use std::io::{self, Cursor};
+
+/// ソケットとの間でフレームを読み書きする
+///
+/// `mini_redis::Connection` は tokio の `TcpStream` にしか対応していないため、
+/// mini-tokio の `TcpStream` を使って同じものを実装している。`mini-tokio` フィーチャーが有効な場合だけ使う。
+/// フレームのパースには `mini_redis::Frame` の `check`・`parse` をそのまま使う。
+pub struct Connection {
+    stream: TcpStream,
+    // ソケットから読み込んだ、まだフレームになっていないデータ
+    buffer: BytesMut,
+}
+
+impl Connection {
+    pub fn new(socket: TcpStream) -> Connection {
+        Connection {
+            stream: socket,
+            buffer: BytesMut::with_capacity(4 * 1024),
+        }
+    }
+
+    /// フレームを1つ読み込む
+    ///
+    /// 相手がコネクションを閉じた場合は `None` を返す。
+    pub async fn read_frame(&mut self) -> mini_redis::Result<Option<Frame>> {
+        let mut chunk = [0; 4 * 1024];
+
+        loop {
+            // バッファに溜まったデータから、フレームを1つパースできるか試す
+            if let Some(frame) = self.parse_frame()? {
+                return Ok(Some(frame));
+            }
+
+            // パースするにはデータが足りないので、ソケットから読み込む
+            let n = self.stream.read(&mut chunk).await?;
+            if n == 0 {
+                // [MEMO]
+                // フレームの途中でコネクションが閉じられた場合はエラーにする
+                if self.buffer.is_empty() {
+                    return Ok(None);
+                } else {
+                    return Err("connection reset by peer".into());
+                }
+            }
+
+            self.buffer.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    fn parse_frame(&mut self) -> mini_redis::Result<Option<Frame>> {
+        let mut buf = Cursor::new(&self.buffer[..]);
+
+        match Frame::check(&mut buf) {
+            Ok(_) => {
+                // `check` によってカーソルはフレームの末尾まで進んでいる
+                let len = buf.position() as usize;
+
+                buf.set_position(0);
+                let frame = Frame::parse(&mut buf)?;
+
+                self.buffer.advance(len);
+                Ok(Some(frame))
+            }
+            Err(frame::Error::Incomplete) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// フレームを1つ書き込む
+    pub async fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        // [MEMO]
+        // `BufWriter` の代わりに、フレーム全体を一度バイト列にしてからまとめて書き込む
+        let mut buf = Vec::new();
+        encode(frame, &mut buf);
+
+        self.stream.write_all(&buf).await
+    }
+}
+
+/// フレームを RESP 形式のバイト列にして `dst` に追加する
+fn encode(frame: &Frame, dst: &mut Vec<u8>) {
+    match frame {
+        Frame::Simple(val) => {
+            dst.push(b'+');
+            dst.extend_from_slice(val.as_bytes());
+            dst.extend_from_slice(b"\r\n");
+        }
+        Frame::Error(val) => {
+            dst.push(b'-');
+            dst.extend_from_slice(val.as_bytes());
+            dst.extend_from_slice(b"\r\n");
+        }
+        Frame::Integer(val) => {
+            dst.push(b':');
+            dst.extend_from_slice(format!("{}\r\n", val).as_bytes());
+        }
+        Frame::Null => {
+            dst.extend_from_slice(b"$-1\r\n");
+        }
+        Frame::Bulk(val) => {
+            dst.push(b'$');
+            dst.extend_from_slice(format!("{}\r\n", val.len()).as_bytes());
+            dst.extend_from_slice(val);
+            dst.extend_from_slice(b"\r\n");
+        }
+        Frame::Array(val) => {
+            dst.push(b'*');
+            dst.extend_from_slice(format!("{}\r\n", val.len()).as_bytes());
+            for entry in val {
+                encode(entry, dst);
+            }
+        }
+    }
+}
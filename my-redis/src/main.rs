@@ -1,13 +1,29 @@
 use bytes::Bytes;
-use mini_redis::{Connection, Frame};
+#[cfg(feature = "mini-tokio")]
+use connection::Connection;
+#[cfg(not(feature = "mini-tokio"))]
+use mini_redis::Connection;
+use mini_redis::Frame;
+use rt::{TcpListener, TcpStream};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use tokio::net::{TcpListener, TcpStream};
+
+// [MEMO]
+// tokio で動かす場合は、これまで通り `mini_redis::Connection` を使う。
+// mini-tokio の `TcpStream` は `mini_redis::Connection` に渡せないので、その場合だけ同じものを実装して使う。
+#[cfg(feature = "mini-tokio")]
+mod connection;
+mod rt;
 
 pub type Db = Arc<Mutex<HashMap<String, Bytes>>>;
 
-#[tokio::main]
-async fn main() {
+// [MEMO]
+// `#[tokio::main]` の代わりに `rt::run` を使い、フィーチャーによって tokio と mini-tokio を切り替える
+fn main() {
+    rt::run(serve());
+}
+
+async fn serve() {
     let listener = TcpListener::bind("127.0.0.1:6379").await.unwrap();
 
     println!("Listening");
@@ -20,7 +36,7 @@ async fn main() {
         let db = db.clone();
 
         println!("Accepted");
-        tokio::spawn(async move {
+        rt::spawn(async move {
             process(socket, db).await;
         });
     }
@@ -29,7 +45,7 @@ async fn main() {
 async fn process(socket: TcpStream, db: Db) {
     use mini_redis::Command::{self, Get, Set};
 
-    // `mini-redis` が提供するコネクションによって、ソケットから来るフレームをパースする
+    // `Connection` によって、ソケットから来るフレームをパースする
     let mut connection = Connection::new(socket);
 
     while let Some(frame) = connection.read_frame().await.unwrap() {
@@ -0,0 +1,46 @@
+//! サーバーが使う非同期ランタイムの抽象化レイヤー
+//!
+//! 通常は tokio を使い、`mini-tokio` フィーチャーを有効にすると mini-tokio を使う。
+//! サーバーのコードはランタイムを直接参照せず、このモジュールを通して
+//! `TcpListener`・`TcpStream`・`spawn` を使う。
+//!
+//! ```text
+//! cargo run -p my-redis                      # tokio で動かす
+//! cargo run -p my-redis --features mini-tokio # mini-tokio で動かす
+//! ```
+
+use std::future::Future;
+
+#[cfg(not(feature = "mini-tokio"))]
+pub use tokio::{
+    net::{TcpListener, TcpStream},
+    spawn,
+};
+
+#[cfg(feature = "mini-tokio")]
+pub use mini_tokio::{
+    net::{TcpListener, TcpStream},
+    spawn,
+};
+
+/// ランタイムを起動し、`future` を実行する
+///
+/// `#[tokio::main]` の代わりに使う。
+#[cfg(not(feature = "mini-tokio"))]
+pub fn run<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    tokio::runtime::Runtime::new().unwrap().block_on(future);
+}
+
+/// ランタイムを起動し、`future` を実行する
+///
+/// `#[tokio::main]` の代わりに使う。
+#[cfg(feature = "mini-tokio")]
+pub fn run<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    mini_tokio::MiniTokio::new().block_on(future);
+}